@@ -0,0 +1,124 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{errors::ToolproofInputError, parser::parse_file, ToolproofTestFile};
+
+/// Kept inside the project, so that only whoever can already edit the tests can edit the cache
+const CACHE_FILE: &str = ".toolproof/parse_cache.json";
+
+/// Bumped whenever the parsed form of a test file changes, since source builds
+/// of Toolproof all share the same version number
+const CACHE_FORMAT: u32 = 1;
+
+#[derive(serde::Serialize, serde::Deserialize, Default)]
+struct CachedFiles {
+    format: u32,
+    /// The Toolproof version that wrote the cache, as parsed files change between versions
+    version: String,
+    files: HashMap<String, CachedFile>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CachedFile {
+    /// The sha256 of the file's source, so that any change to the file is re-parsed
+    hash: String,
+    parsed: ToolproofTestFile,
+}
+
+/// Parsed test files from previous runs, stored on disk
+/// so that unchanged files don't need to be parsed again
+pub struct ParsedFileCache {
+    /// Where the cache is read from and written to, or `None` if caching is disabled
+    location: Option<PathBuf>,
+    version: String,
+    cached: CachedFiles,
+    seen: HashSet<String>,
+    changed: bool,
+}
+
+impl ParsedFileCache {
+    /// Loads the cache for a working directory, starting afresh if the cache
+    /// is missing, unreadable, or from another version of Toolproof.
+    /// If `enabled` is false, every file is parsed and nothing is written to disk.
+    pub fn load(working_directory: &Path, version: &str, enabled: bool) -> Self {
+        let location = enabled.then(|| working_directory.join(CACHE_FILE));
+
+        let cached = location
+            .as_ref()
+            .and_then(|location| fs::read(location).ok())
+            .and_then(|contents| serde_json::from_slice::<CachedFiles>(&contents).ok())
+            .filter(|cached| cached.format == CACHE_FORMAT && cached.version == version)
+            .unwrap_or_default();
+
+        Self {
+            location,
+            version: version.to_string(),
+            cached,
+            seen: HashSet::new(),
+            changed: false,
+        }
+    }
+
+    /// Parses a test file, or returns the cached result if the file hasn't changed
+    pub fn parse_file(
+        &mut self,
+        source: &str,
+        p: PathBuf,
+    ) -> Result<ToolproofTestFile, ToolproofInputError> {
+        let key = p.to_string_lossy().into_owned();
+        let hash = format!("{:x}", Sha256::digest(source.as_bytes()));
+        self.seen.insert(key.clone());
+
+        if let Some(cached) = self.cached.files.get(&key) {
+            if cached.hash == hash {
+                return Ok(cached.parsed.clone());
+            }
+        }
+
+        let parsed = parse_file(source, p)?;
+        self.cached.files.insert(
+            key,
+            CachedFile {
+                hash,
+                parsed: parsed.clone(),
+            },
+        );
+        self.changed = true;
+
+        Ok(parsed)
+    }
+
+    /// Writes the cache back to disk, dropping any files that weren't seen in this run.
+    /// Failing to write the cache isn't an error, as it will be rebuilt next time.
+    pub fn save(mut self) {
+        let Some(location) = self.location else {
+            return;
+        };
+
+        let before = self.cached.files.len();
+        self.cached.files.retain(|key, _| self.seen.contains(key));
+        if !self.changed && self.cached.files.len() == before {
+            return;
+        }
+
+        self.cached.format = CACHE_FORMAT;
+        self.cached.version = self.version;
+        let Ok(contents) = serde_json::to_vec(&self.cached) else {
+            return;
+        };
+        if let Some(parent) = location.parent() {
+            _ = fs::create_dir_all(parent);
+        }
+        // Written alongside and then moved into place, so that runs happening
+        // at the same time never read a partially written cache
+        let partial = location.with_extension(format!("{}.partial", std::process::id()));
+        if fs::write(&partial, contents).is_ok() {
+            _ = fs::rename(&partial, &location);
+        }
+    }
+}
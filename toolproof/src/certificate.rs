@@ -0,0 +1,55 @@
+use std::sync::OnceLock;
+
+use base64::Engine;
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+use rustls::pki_types::CertificateDer;
+use sha2::{Digest, Sha256};
+
+/// The certificate that sites served over https are hosted with, created once per run.
+/// Only this certificate is trusted by the browser and HTTP client, so that
+/// tests visiting any other https site still have their certificates checked.
+pub struct LocalCertificate {
+    /// The localhost certificate, followed by the certificate authority that signed it
+    pub chain: Vec<CertificateDer<'static>>,
+    /// The PKCS #8 private key of the localhost certificate
+    pub key_der: Vec<u8>,
+    /// The certificate authority, which the HTTP client trusts as a root
+    pub ca_der: CertificateDer<'static>,
+    /// The base64 sha256 of the localhost certificate's public key,
+    /// for Chrome's `--ignore-certificate-errors-spki-list`
+    pub spki_sha256: String,
+}
+
+static LOCAL_CERTIFICATE: OnceLock<Result<LocalCertificate, String>> = OnceLock::new();
+
+/// Returns the certificate for this run, creating it the first time it is needed
+pub fn local_certificate() -> Result<&'static LocalCertificate, String> {
+    LOCAL_CERTIFICATE
+        .get_or_init(|| generate().map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+fn generate() -> Result<LocalCertificate, rcgen::Error> {
+    let ca_key = KeyPair::generate()?;
+    let mut ca_params = CertificateParams::new(Vec::<String>::new())?;
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "Toolproof local certificate authority");
+    let ca_cert = ca_params.self_signed(&ca_key)?;
+
+    let key = KeyPair::generate()?;
+    let cert = CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()])?
+        .signed_by(&key, &ca_cert, &ca_key)?;
+
+    let spki_sha256 =
+        base64::engine::general_purpose::STANDARD.encode(Sha256::digest(key.public_key_der()));
+
+    Ok(LocalCertificate {
+        chain: vec![cert.der().clone(), ca_cert.der().clone()],
+        key_der: key.serialize_der(),
+        ca_der: ca_cert.der().clone(),
+        spki_sha256,
+    })
+}
@@ -2,11 +2,14 @@ use std::{
     collections::HashMap,
     fs,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{ExitStatus, Stdio},
     str::from_utf8,
-    sync::Arc,
-    time::Duration,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use actix_web::dev::ServerHandle;
@@ -16,7 +19,13 @@ use tokio::{process::Command, task::JoinHandle};
 use wax::Glob;
 
 use crate::{
-    definitions::browser::BrowserWindow, errors::ToolproofTestFailure, universe::Universe,
+    definitions::{
+        browser::{BrowserEmulation, BrowserWindow},
+        http::HttpResponse,
+    },
+    errors::ToolproofTestFailure,
+    logging::log_println,
+    universe::Universe,
 };
 
 #[derive(Debug)]
@@ -25,14 +34,86 @@ pub struct CommandOutput {
     pub stderr: String,
 }
 
+/// Reads `.env` style `KEY=VALUE` lines, ignoring blank lines and `#` comments.
+/// Lines can start with `export`, and values can be wrapped in quotes.
+pub fn parse_env_lines(contents: &str) -> Result<Vec<(String, String)>, String> {
+    let mut env_vars = vec![];
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((env_name, env_value)) = line.split_once('=') else {
+            return Err(format!(
+                "line {}: expected KEY=VALUE, found \"{line}\"",
+                i + 1
+            ));
+        };
+
+        let env_name = env_name.trim();
+        if env_name.is_empty() {
+            return Err(format!("line {}: missing variable name", i + 1));
+        }
+
+        let env_value = env_value.trim();
+        let env_value = [('"', '"'), ('\'', '\'')]
+            .iter()
+            .find_map(|(open, close)| {
+                env_value
+                    .strip_prefix(*open)
+                    .and_then(|v| v.strip_suffix(*close))
+            })
+            .unwrap_or(env_value);
+
+        env_vars.push((env_name.to_string(), env_value.to_string()));
+    }
+
+    Ok(env_vars)
+}
+
+/// Creates a token that no other test shares, in this run or any other run of Toolproof,
+/// made only of lowercase letters, digits, and underscores so it can name most resources
+pub fn unique_test_id() -> String {
+    static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    format!(
+        "toolproof_{started}_{}_{}",
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
 pub struct Civilization<'u> {
     pub tmp_dir: Option<tempfile::TempDir>,
+    /// Shared by every step of this test, for naming resources outside of the temporary directory
+    pub unique_id: String,
     pub last_command_output: Option<CommandOutput>,
+    /// The response to the last request made with `I make a {method} request to {url}`
+    pub last_response: Option<HttpResponse>,
     pub assigned_server_port: Option<u16>,
+    pub serving_https: bool,
+    /// Path prefixes that the hosted server forwards to an upstream server
+    pub proxies: Vec<(String, String)>,
     pub window: Option<BrowserWindow>,
+    /// Browser settings changed by this test, applied to each page it loads
+    pub emulation: BrowserEmulation,
     pub threads: Vec<JoinHandle<Result<(), std::io::Error>>>,
     pub handles: Vec<ServerHandle>,
     pub env_vars: HashMap<String, String>,
+    /// The directory of the file whose steps are currently running
+    pub file_directory: PathBuf,
+    /// The timeout of the step that is currently running, in seconds
+    pub step_timeout: u64,
+    /// Screenshots that didn't match their baseline image, as `(baseline, actual)` paths
+    pub changed_images: Vec<(PathBuf, PathBuf)>,
     pub universe: Arc<Universe<'u>>,
 }
 
@@ -40,27 +121,8 @@ impl<'u> Civilization<'u> {
     pub async fn shutdown(mut self) {
         self.stop_servers().await;
 
-        if let Some(BrowserWindow::Chrome {
-            page,
-            context_id,
-            browser,
-        }) = self.window
-        {
-            match tokio::time::timeout(Duration::from_secs(5), async {
-                if let Err(e) = page.close().await {
-                    eprintln!("[toolproof] Warning: Failed to close browser window: {e}");
-                }
-                if let Err(e) = browser.dispose_browser_context(context_id).await {
-                    eprintln!("[toolproof] Warning: Failed to dispose browser context: {e}");
-                }
-            })
-            .await
-            {
-                Ok(()) => {}
-                Err(_) => {
-                    eprintln!("[toolproof] Warning: Timed out cleaning up browser window");
-                }
-            }
+        if let Some(window) = self.window.take() {
+            window.close().await;
         }
     }
 }
@@ -86,9 +148,22 @@ impl<'u> Civilization<'u> {
         self.assigned_server_port = None;
     }
 
+    /// The origin of the server hosted for this test, e.g. `http://localhost:1234`
+    pub fn server_origin(&mut self) -> String {
+        let scheme = if self.serving_https { "https" } else { "http" };
+        format!("{scheme}://localhost:{}", self.ensure_port())
+    }
+
     pub fn tmp_dir(&mut self) -> PathBuf {
         if self.tmp_dir.is_none() {
-            self.tmp_dir = Some(tempdir().expect("testing on a system with a temp dir"));
+            let tmp_dir = tempdir().expect("testing on a system with a temp dir");
+            if self.universe.ctx.params.verbose {
+                log_println!(
+                    "[toolproof] Created test directory: {}",
+                    tmp_dir.path().to_string_lossy()
+                );
+            }
+            self.tmp_dir = Some(tmp_dir);
         }
         self.tmp_dir
             .as_ref()
@@ -107,42 +182,67 @@ impl<'u> Civilization<'u> {
     }
 
     pub fn write_file(&mut self, filename: &str, contents: &str) {
+        // let contents = self.process_substitutions(contents);
+
+        self.write_file_bytes(filename, contents.as_bytes());
+    }
+
+    pub fn write_file_bytes(&mut self, filename: &str, contents: &[u8]) {
         let file_path = self.tmp_file_path(filename);
         fs::create_dir_all(file_path.parent().unwrap()).unwrap();
 
-        // let contents = self.process_substitutions(contents);
-
         let mut file = std::fs::File::create(&file_path).unwrap();
-        file.write_all(contents.as_bytes()).unwrap();
+        file.write_all(contents).unwrap();
     }
 
     pub fn read_file(&mut self, filename: &str) -> Result<String, ToolproofTestFailure> {
         let file_path = self.tmp_file_path(filename);
-        let mut file = std::fs::File::open(&file_path).map_err(|e| {
-            let msg = match e.kind() {
-                std::io::ErrorKind::NotFound => "the file does not exist".to_string(),
-                _ => "the file was not readable".to_string(),
-            };
-            ToolproofTestFailure::Custom { msg }
-        })?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .map_err(|_| ToolproofTestFailure::Custom {
-                msg: "the file was not valid UTF-8".to_string(),
-            })?;
-        Ok(contents)
+        read_file_at(&file_path)
+    }
+
+    pub fn read_file_bytes(&mut self, filename: &str) -> Result<Vec<u8>, ToolproofTestFailure> {
+        let file_path = self.tmp_file_path(filename);
+        read_file_bytes_at(&file_path)
+    }
+
+    /// The path to a file relative to the current test file, rather than the temp dir
+    pub fn source_file_path(&self, filename: &str) -> PathBuf {
+        self.universe
+            .ctx
+            .working_directory
+            .join(&self.file_directory)
+            .join(filename)
     }
 
+    pub fn read_source_file(&self, filename: &str) -> Result<String, ToolproofTestFailure> {
+        read_file_at(&self.source_file_path(filename))
+    }
+
+    pub fn read_source_file_bytes(&self, filename: &str) -> Result<Vec<u8>, ToolproofTestFailure> {
+        read_file_bytes_at(&self.source_file_path(filename))
+    }
+
+    /// Lists the temporary directory as an indented tree, sorted by path
+    /// so that the same files always produce the same tree
     pub fn get_file_tree(&mut self) -> String {
         let glob = Glob::new("**/*").expect("Valid glob");
         let base_dir = self.tmp_file_path(".");
-        let walk = glob.walk(&base_dir).flatten();
-        let entries: Vec<String> = walk
-            .filter_map(|entry| {
-                let file = entry
+        let mut files: Vec<PathBuf> = glob
+            .walk(&base_dir)
+            .flatten()
+            .map(|entry| {
+                entry
                     .path()
                     .strip_prefix(&base_dir)
-                    .expect("Valid file path");
+                    .expect("Valid file path")
+                    .to_path_buf()
+            })
+            .collect();
+        files.sort();
+
+        let entries: Vec<String> = files
+            .iter()
+            .filter_map(|file| {
                 let indentation = "  ".repeat(file.components().count().saturating_sub(1));
                 file.file_name().map(|filename| {
                     format!(
@@ -156,6 +256,16 @@ impl<'u> Civilization<'u> {
         entries.join("\n")
     }
 
+    /// Counts the files in the temporary directory, not including directories
+    pub fn get_file_count(&mut self) -> usize {
+        let glob = Glob::new("**/*").expect("Valid glob");
+        let base_dir = self.tmp_file_path(".");
+        glob.walk(&base_dir)
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+            .count()
+    }
+
     pub fn assert_file_exists(&mut self, filename: &str) {
         if !self.check_file_exists(filename) {
             panic!(
@@ -231,3 +341,33 @@ impl<'u> Civilization<'u> {
         Ok(output.status)
     }
 }
+
+fn open_file_at(file_path: &Path) -> Result<fs::File, ToolproofTestFailure> {
+    std::fs::File::open(file_path).map_err(|e| {
+        let msg = match e.kind() {
+            std::io::ErrorKind::NotFound => "the file does not exist".to_string(),
+            _ => "the file was not readable".to_string(),
+        };
+        ToolproofTestFailure::Custom { msg }
+    })
+}
+
+fn read_file_at(file_path: &Path) -> Result<String, ToolproofTestFailure> {
+    let mut file = open_file_at(file_path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)
+        .map_err(|_| ToolproofTestFailure::Custom {
+            msg: "the file was not valid UTF-8".to_string(),
+        })?;
+    Ok(contents)
+}
+
+fn read_file_bytes_at(file_path: &Path) -> Result<Vec<u8>, ToolproofTestFailure> {
+    let mut file = open_file_at(file_path)?;
+    let mut contents = vec![];
+    file.read_to_end(&mut contents)
+        .map_err(|_| ToolproofTestFailure::Custom {
+            msg: "the file was not readable".to_string(),
+        })?;
+    Ok(contents)
+}
@@ -40,18 +40,23 @@ fn value_contains_value(
                 Ok(false)
             }
         },
-        // (Array(_), Null) => todo!(),
-        // (Array(_), Bool(_)) => todo!(),
-        // (Array(_), Number(_)) => todo!(),
-        // (Array(_), String(_)) => todo!(),
-        // (Array(_), Array(_)) => todo!(),
-        // (Array(_), Object(_)) => todo!(),
+        // An array contains each item of an expected array
+        (Array(a), Array(e)) => Ok(e.iter().all(|e| a.contains(e))),
+        // An array contains a value equal to one of its items,
+        // or an object that is contained in one of its items
+        (Array(a), _) => Ok(a.iter().any(|item| {
+            item == expected
+                || (item.is_object()
+                    && expected.is_object()
+                    && value_contains_value(item, expected).unwrap_or(false))
+        })),
+        // An object contains each key and value of an expected object
+        (Object(o), Object(e)) => Ok(e.iter().all(|(k, v)| o.get(k) == Some(v))),
         // (Object(_), Null) => todo!(),
         // (Object(_), Bool(_)) => todo!(),
         // (Object(_), Number(_)) => todo!(),
         // (Object(_), String(_)) => todo!(),
         // (Object(_), Array(_)) => todo!(),
-        // (Object(_), Object(_)) => todo!(),
         _ => {
             Err(ToolproofStepError::Internal(ToolproofInternalError::Custom { msg: format!(
                 "A comparison for these values has not been implemented.\n---\n{}\n---\ncannot compare with\n---\n{}\n---",
@@ -62,6 +67,45 @@ fn value_contains_value(
     }
 }
 
+/// Compares values exactly, except for numbers which may differ by the tolerance.
+/// Numbers given as strings (e.g. inline step values) are compared as numbers.
+fn value_approximately_equals(
+    base: &serde_json::Value,
+    expected: &serde_json::Value,
+    tolerance: f64,
+) -> bool {
+    use serde_json::Value::*;
+
+    let as_number = |v: &serde_json::Value| match v {
+        Number(n) => n.as_f64(),
+        String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+
+    match (base, expected) {
+        (Number(_), Number(_) | String(_)) | (String(_), Number(_)) => {
+            match (as_number(base), as_number(expected)) {
+                (Some(a), Some(b)) => (a - b).abs() <= tolerance,
+                _ => false,
+            }
+        }
+        (Array(a), Array(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| value_approximately_equals(a, b, tolerance))
+        }
+        (Object(a), Object(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(k, a)| {
+                    b.get(k)
+                        .is_some_and(|b| value_approximately_equals(a, b, tolerance))
+                })
+        }
+        _ => base == expected,
+    }
+}
+
 fn value_is_empty(val: &serde_json::Value) -> bool {
     match val {
         serde_json::Value::Null => true,
@@ -161,7 +205,11 @@ mod contain {
 }
 
 mod exactly {
-    use crate::errors::ToolproofTestFailure;
+    use crate::{
+        differ::diff_values,
+        errors::{ToolproofInputError, ToolproofTestFailure},
+        platforms::normalize_line_endings,
+    };
 
     use super::*;
 
@@ -188,17 +236,91 @@ mod exactly {
             if base_value == expected {
                 Ok(())
             } else {
+                // Strings are diffed as their text, and other values as pretty JSON
+                let diff = match (&expected, &base_value) {
+                    (serde_json::Value::String(expected), serde_json::Value::String(actual)) => {
+                        diff_values(expected, actual)
+                    }
+                    _ => diff_values(
+                        &serde_json::to_string_pretty(&expected).expect("should be json-able"),
+                        &serde_json::to_string_pretty(&base_value).expect("should be json-able"),
+                    ),
+                };
                 Err(ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
                     msg: format!(
-                        "The value\n---\n{}\n---\nshould be exactly the following value, but is not\n---\n{}\n---",
-                        serde_json::to_string(&base_value).expect("should be yaml-able"),
-                        serde_json::to_string(&expected).expect("should be yaml-able")
+                        "The value should be exactly the expected value, but is not (- expected, + actual)\n---\n{diff}---"
                     ),
                 }))
             }
         }
     }
 
+    pub struct ExactlyFile;
+
+    inventory::submit! {
+        &ExactlyFile as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for ExactlyFile {
+        fn segments(&self) -> &'static str {
+            "be exactly the file {filename}"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let filename = args.get_string("filename")?;
+
+            // Files created during the test are used before files next to the test file
+            let contents = if civ.tmp_file_path(&filename).exists() {
+                civ.read_file(&filename)?
+            } else {
+                civ.read_source_file(&filename)?
+            };
+
+            // Strings are compared with the file's text, and other values with its parsed content
+            let (expected, actual) = match &base_value {
+                serde_json::Value::String(s) => {
+                    (normalize_line_endings(&contents), normalize_line_endings(s))
+                }
+                _ => {
+                    let expected: serde_json::Value =
+                        serde_yaml::from_str(&contents).map_err(|e| {
+                            ToolproofInputError::StepError {
+                                reason: format!(
+                                    "The file {filename} could not be parsed as JSON or YAML \
+                                     to compare with a {} value: {e}",
+                                    value_type(&base_value)
+                                ),
+                            }
+                        })?;
+                    if base_value == expected {
+                        return Ok(());
+                    }
+                    (
+                        serde_json::to_string_pretty(&expected).expect("should be json-able"),
+                        serde_json::to_string_pretty(&base_value).expect("should be json-able"),
+                    )
+                }
+            };
+
+            if expected == actual {
+                return Ok(());
+            }
+
+            let diff = diff_values(&expected, &actual);
+            Err(ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+                msg: format!(
+                    "The value should be exactly the content of {filename}, but is not (- expected, + actual)\n---\n{diff}---"
+                ),
+            }))
+        }
+    }
+
     pub struct NotExactly;
 
     inventory::submit! {
@@ -234,6 +356,81 @@ mod exactly {
     }
 }
 
+mod approximately {
+    use crate::errors::ToolproofTestFailure;
+
+    use super::*;
+
+    const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+    fn assert_approximately(
+        base_value: &serde_json::Value,
+        expected: &serde_json::Value,
+        tolerance: f64,
+    ) -> Result<(), ToolproofStepError> {
+        if value_approximately_equals(base_value, expected, tolerance) {
+            Ok(())
+        } else {
+            Err(ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+                msg: format!(
+                    "The value\n---\n{}\n---\nshould be within {tolerance} of the following value, but is not\n---\n{}\n---",
+                    serde_json::to_string(&base_value).expect("should be yaml-able"),
+                    serde_json::to_string(&expected).expect("should be yaml-able")
+                ),
+            }))
+        }
+    }
+
+    pub struct Approximately;
+
+    inventory::submit! {
+        &Approximately as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for Approximately {
+        fn segments(&self) -> &'static str {
+            "be approximately {expected}"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let expected = args.get_value("expected")?;
+
+            assert_approximately(&base_value, &expected, DEFAULT_TOLERANCE)
+        }
+    }
+
+    pub struct ApproximatelyWithin;
+
+    inventory::submit! {
+        &ApproximatelyWithin as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for ApproximatelyWithin {
+        fn segments(&self) -> &'static str {
+            "be approximately {expected} within {tolerance}"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let expected = args.get_value("expected")?;
+            let tolerance = args.get_f64("tolerance")?;
+
+            assert_approximately(&base_value, &expected, tolerance)
+        }
+    }
+}
+
 mod empty {
     use crate::errors::ToolproofTestFailure;
 
@@ -305,3 +502,482 @@ mod empty {
         }
     }
 }
+
+mod schema {
+    use crate::errors::{ToolproofInputError, ToolproofTestFailure};
+
+    use super::*;
+
+    /// Schemas can be given inline, or as a path to a JSON or YAML file
+    /// relative to the test file
+    fn load_schema(
+        schema: serde_json::Value,
+        civ: &Civilization,
+    ) -> Result<serde_json::Value, ToolproofStepError> {
+        let serde_json::Value::String(schema) = schema else {
+            return Ok(schema);
+        };
+
+        if let Ok(inline) = serde_json::from_str::<serde_json::Value>(&schema) {
+            if inline.is_object() {
+                return Ok(inline);
+            }
+        }
+
+        let schema_path = civ.source_file_path(&schema);
+
+        let contents = std::fs::read_to_string(&schema_path).map_err(|e| {
+            ToolproofStepError::External(ToolproofInputError::StepError {
+                reason: format!("Could not read the schema file {schema}: {e}"),
+            })
+        })?;
+
+        serde_yaml::from_str(&contents).map_err(|e| {
+            ToolproofStepError::External(ToolproofInputError::StepError {
+                reason: format!("Could not parse the schema file {schema}: {e}"),
+            })
+        })
+    }
+
+    pub struct MatchSchema;
+
+    inventory::submit! {
+        &MatchSchema as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for MatchSchema {
+        fn segments(&self) -> &'static str {
+            "match the schema {schema}"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let schema = load_schema(args.get_value("schema")?, civ)?;
+
+            let validator = jsonschema::validator_for(&schema).map_err(|e| {
+                ToolproofStepError::External(ToolproofInputError::StepError {
+                    reason: format!("The schema is not a valid JSON Schema: {e}"),
+                })
+            })?;
+
+            let errors = validator
+                .iter_errors(&base_value)
+                .map(|e| {
+                    let path = e.instance_path.to_string();
+                    if path.is_empty() {
+                        format!("  - {e}")
+                    } else {
+                        format!("  - at {path}: {e}")
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            if errors.is_empty() {
+                Ok(())
+            } else {
+                Err(ToolproofStepError::Assertion(
+                    ToolproofTestFailure::Custom {
+                        msg: format!(
+                            "The value\n---\n{}\n---\ndoes not match the schema:\n{}",
+                            serde_json::to_string(&base_value).expect("should be yaml-able"),
+                            errors.join("\n")
+                        ),
+                    },
+                ))
+            }
+        }
+    }
+}
+
+mod lines {
+    use crate::errors::{ToolproofInputError, ToolproofTestFailure};
+
+    use super::*;
+
+    /// The number of lines in the value, and the number of lines expected by the step
+    fn line_counts(
+        base_value: &serde_json::Value,
+        args: &SegmentArgs<'_>,
+    ) -> Result<(usize, usize), ToolproofStepError> {
+        let serde_json::Value::String(s) = base_value else {
+            return Err(ToolproofInputError::StepRequirementsNotMet {
+                reason: format!(
+                    "Line counts can only be checked on a string value, but received a {} value",
+                    value_type(base_value)
+                ),
+            }
+            .into());
+        };
+
+        let n = args.get_f64("n")?;
+        if n < 0.0 || n.fract() != 0.0 {
+            return Err(ToolproofInputError::StepError {
+                reason: format!("The number of lines must be a whole number, but was {n}"),
+            }
+            .into());
+        }
+
+        Ok((s.lines().count(), n as usize))
+    }
+
+    fn line_count_failure(
+        base_value: &serde_json::Value,
+        expectation: String,
+        count: usize,
+    ) -> ToolproofStepError {
+        ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+            msg: format!(
+                "The value\n---\n{}\n---\nshould have {expectation}, but has {count}",
+                base_value.as_str().unwrap_or_default(),
+            ),
+        })
+    }
+
+    pub struct HaveLines;
+
+    inventory::submit! {
+        &HaveLines as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for HaveLines {
+        fn segments(&self) -> &'static str {
+            "have {n} lines"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let (count, n) = line_counts(&base_value, args)?;
+
+            if count == n {
+                Ok(())
+            } else {
+                Err(line_count_failure(&base_value, format!("{n} lines"), count))
+            }
+        }
+    }
+
+    pub struct HaveMoreLines;
+
+    inventory::submit! {
+        &HaveMoreLines as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for HaveMoreLines {
+        fn segments(&self) -> &'static str {
+            "have more than {n} lines"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let (count, n) = line_counts(&base_value, args)?;
+
+            if count > n {
+                Ok(())
+            } else {
+                Err(line_count_failure(
+                    &base_value,
+                    format!("more than {n} lines"),
+                    count,
+                ))
+            }
+        }
+    }
+
+    pub struct HaveFewerLines;
+
+    inventory::submit! {
+        &HaveFewerLines as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for HaveFewerLines {
+        fn segments(&self) -> &'static str {
+            "have fewer than {n} lines"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let (count, n) = line_counts(&base_value, args)?;
+
+            if count < n {
+                Ok(())
+            } else {
+                Err(line_count_failure(
+                    &base_value,
+                    format!("fewer than {n} lines"),
+                    count,
+                ))
+            }
+        }
+    }
+}
+
+mod length {
+    use crate::errors::{ToolproofInputError, ToolproofTestFailure};
+
+    use super::*;
+
+    pub struct HaveLength;
+
+    inventory::submit! {
+        &HaveLength as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for HaveLength {
+        fn segments(&self) -> &'static str {
+            "have length {n}"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let length = match &base_value {
+                serde_json::Value::Array(a) => a.len(),
+                serde_json::Value::Object(o) => o.len(),
+                serde_json::Value::String(s) => s.chars().count(),
+                _ => {
+                    let reason = format!(
+                        "Only arrays, objects, and strings have a length, but received a {} value",
+                        value_type(&base_value)
+                    );
+                    return Err(ToolproofInputError::StepRequirementsNotMet { reason }.into());
+                }
+            };
+
+            let n = args.get_f64("n")?;
+
+            if length as f64 == n {
+                Ok(())
+            } else {
+                Err(ToolproofStepError::Assertion(
+                    ToolproofTestFailure::Custom {
+                        msg: format!(
+                            "The value\n---\n{}\n---\nshould have length {n}, but has length {length}",
+                            serde_json::to_string(&base_value).expect("should be yaml-able"),
+                        ),
+                    },
+                ))
+            }
+        }
+    }
+}
+
+mod sorted {
+    use std::cmp::Ordering;
+
+    use crate::errors::{ToolproofInputError, ToolproofTestFailure};
+
+    use super::*;
+
+    /// Finds the first item that is out of order with the item before it
+    fn first_unsorted_index(
+        base_value: &serde_json::Value,
+        descending: bool,
+    ) -> Result<Option<usize>, ToolproofStepError> {
+        let serde_json::Value::Array(items) = base_value else {
+            let reason = format!(
+                "Only arrays can be checked for sorting, but received a {} value",
+                value_type(base_value)
+            );
+            return Err(ToolproofInputError::StepRequirementsNotMet { reason }.into());
+        };
+
+        let all_numbers = items.iter().all(|item| item.is_number());
+        if !all_numbers && !items.iter().all(|item| item.is_string()) {
+            let mut types = items.iter().map(value_type).collect::<Vec<_>>();
+            types.sort_unstable();
+            types.dedup();
+            let reason = format!(
+                "Sorting can only be checked on arrays of all numbers or all strings, but the array contains {} values",
+                types.join(", ")
+            );
+            return Err(ToolproofInputError::StepRequirementsNotMet { reason }.into());
+        }
+
+        let compare = |a: &serde_json::Value, b: &serde_json::Value| {
+            if all_numbers {
+                let a = a.as_f64().unwrap_or_default();
+                a.total_cmp(&b.as_f64().unwrap_or_default())
+            } else {
+                a.as_str().cmp(&b.as_str())
+            }
+        };
+
+        let out_of_order = if descending {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        };
+
+        Ok(items
+            .windows(2)
+            .position(|pair| compare(&pair[0], &pair[1]) == out_of_order)
+            .map(|index| index + 1))
+    }
+
+    fn check_sorted(
+        base_value: &serde_json::Value,
+        descending: bool,
+    ) -> Result<(), ToolproofStepError> {
+        let Some(index) = first_unsorted_index(base_value, descending)? else {
+            return Ok(());
+        };
+
+        let order = if descending {
+            "descending"
+        } else {
+            "ascending"
+        };
+        Err(ToolproofStepError::Assertion(
+            ToolproofTestFailure::Custom {
+                msg: format!(
+                    "The array\n---\n{}\n---\nshould be sorted in {order} order, but item {index} ({}) is out of order with the item before it ({})",
+                    serde_json::to_string(base_value).expect("should be yaml-able"),
+                    base_value[index],
+                    base_value[index - 1],
+                ),
+            },
+        ))
+    }
+
+    pub struct BeSorted;
+
+    inventory::submit! {
+        &BeSorted as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for BeSorted {
+        fn segments(&self) -> &'static str {
+            "be sorted"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            _args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            check_sorted(&base_value, false)
+        }
+    }
+
+    pub struct BeSortedDescending;
+
+    inventory::submit! {
+        &BeSortedDescending as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for BeSortedDescending {
+        fn segments(&self) -> &'static str {
+            "be sorted descending"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            _args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            check_sorted(&base_value, true)
+        }
+    }
+}
+
+mod valid {
+    use crate::errors::ToolproofTestFailure;
+
+    use super::*;
+
+    /// Checks that a string value parses in some format. Other values have
+    /// already been parsed, so are always valid.
+    fn check_parses<E: std::fmt::Display>(
+        base_value: &serde_json::Value,
+        format: &str,
+        parse: impl Fn(&str) -> Result<(), E>,
+    ) -> Result<(), ToolproofStepError> {
+        let serde_json::Value::String(s) = base_value else {
+            return Ok(());
+        };
+
+        parse(s).map_err(|e| {
+            ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+                msg: format!(
+                    "The value should be valid {format}, but is not ({e}):\n---\n{s}\n---"
+                ),
+            })
+        })
+    }
+
+    pub struct ValidJson;
+
+    inventory::submit! {
+        &ValidJson as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for ValidJson {
+        fn segments(&self) -> &'static str {
+            "be valid json"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            _args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            check_parses(&base_value, "JSON", |s| {
+                serde_json::from_str::<serde_json::Value>(s).map(|_| ())
+            })
+        }
+    }
+
+    pub struct ValidYaml;
+
+    inventory::submit! {
+        &ValidYaml as &dyn ToolproofAssertion
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for ValidYaml {
+        fn segments(&self) -> &'static str {
+            "be valid yaml"
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            _args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            check_parses(&base_value, "YAML", |s| {
+                serde_yaml::from_str::<serde_yaml::Value>(s).map(|_| ())
+            })
+        }
+    }
+}
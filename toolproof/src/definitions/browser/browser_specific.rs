@@ -1,8 +1,24 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
-use chromiumoxide::cdp::browser_protocol::page::CaptureScreenshotFormat;
+use chromiumoxide::cdp::browser_protocol::accessibility::{AxNode, AxValue};
+use chromiumoxide::cdp::browser_protocol::dom::DescribeNodeParams;
+use chromiumoxide::cdp::browser_protocol::network::{
+    EventLoadingFailed, EventLoadingFinished, EventRequestWillBeSent, EventResponseReceived,
+    ResourceType,
+};
+use chromiumoxide::cdp::browser_protocol::page::{CaptureScreenshotFormat, FrameId};
+use chromiumoxide::cdp::js_protocol::runtime::{CallFunctionOnParams, ExecutionContextId};
+use chromiumoxide::error::CdpError;
+use chromiumoxide::layout::Point;
+use futures::stream::BoxStream;
+use futures::StreamExt;
 
-use crate::errors::{ToolproofInputError, ToolproofStepError, ToolproofTestFailure};
+use crate::errors::{
+    ToolproofInputError, ToolproofInternalError, ToolproofStepError, ToolproofTestFailure,
+};
 
 pub fn chrome_image_format(
     filepath: &PathBuf,
@@ -29,6 +45,103 @@ pub fn chrome_image_format(
     }
 }
 
+/// A network event from a page, from one of the listeners merged by [`chrome_network_events`]
+pub enum NetworkEvent {
+    Sent(Arc<EventRequestWillBeSent>),
+    Response(Arc<EventResponseReceived>),
+    Finished(Arc<EventLoadingFinished>),
+    Failed(Arc<EventLoadingFailed>),
+}
+
+/// Listens for the network events of a page as a single stream.
+/// Chrome sends each kind of event to its own listener, so while events of one kind
+/// arrive in order, a request can end before the event that started it is seen.
+pub async fn chrome_network_events(
+    page: &chromiumoxide::Page,
+) -> Result<BoxStream<'static, NetworkEvent>, CdpError> {
+    let sent = page.event_listener::<EventRequestWillBeSent>().await?;
+    let responses = page.event_listener::<EventResponseReceived>().await?;
+    let finished = page.event_listener::<EventLoadingFinished>().await?;
+    let failed = page.event_listener::<EventLoadingFailed>().await?;
+
+    Ok(futures::stream::select_all([
+        sent.map(NetworkEvent::Sent).boxed(),
+        responses.map(NetworkEvent::Response).boxed(),
+        finished.map(NetworkEvent::Finished).boxed(),
+        failed.map(NetworkEvent::Failed).boxed(),
+    ])
+    .boxed())
+}
+
+/// Whether the request keeps its connection open to receive updates, rather than loading once
+pub fn is_streaming_request(event: &EventRequestWillBeSent) -> bool {
+    matches!(
+        event.r#type,
+        Some(ResourceType::EventSource | ResourceType::WebSocket)
+    )
+}
+
+/// Records each request from the page that returns an error status or fails to load,
+/// for as long as the page is open. Cancelled requests, such as from navigating away, are ignored.
+pub async fn track_chrome_failed_requests(
+    page: &chromiumoxide::Page,
+    failed_requests: Arc<Mutex<Vec<serde_json::Value>>>,
+) -> Result<(), CdpError> {
+    let mut events = chrome_network_events(page).await?;
+
+    tokio::spawn(async move {
+        // Failure events only carry the request id, so urls are kept to report them with
+        // until their request ends
+        let mut urls = HashMap::new();
+        // Requests that ended before they were seen being sent, with the error if they failed
+        let mut ended_early = HashMap::new();
+
+        let report = |url: &str, error: &str| {
+            failed_requests.lock().unwrap().push(serde_json::json!({
+                "url": url,
+                "error": error,
+            }));
+        };
+
+        while let Some(event) = events.next().await {
+            match event {
+                NetworkEvent::Sent(event) => match ended_early.remove(&event.request_id) {
+                    Some(Some(error)) => report(&event.request.url, &error),
+                    Some(None) => {}
+                    None => {
+                        urls.insert(event.request_id.clone(), event.request.url.clone());
+                    }
+                },
+                NetworkEvent::Response(event) => {
+                    if event.response.status >= 400 {
+                        failed_requests.lock().unwrap().push(serde_json::json!({
+                            "url": event.response.url,
+                            "status": event.response.status,
+                        }));
+                    }
+                }
+                NetworkEvent::Finished(event) => {
+                    if urls.remove(&event.request_id).is_none() {
+                        ended_early.insert(event.request_id.clone(), None);
+                    }
+                }
+                NetworkEvent::Failed(event) => {
+                    let error = (event.canceled != Some(true)).then(|| event.error_text.clone());
+                    match (urls.remove(&event.request_id), error) {
+                        (Some(url), Some(error)) => report(&url, &error),
+                        (Some(_), None) => {}
+                        (None, error) => {
+                            ended_early.insert(event.request_id.clone(), error);
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
 pub async fn wait_for_chrome_element_selector(
     page: &chromiumoxide::Page,
     selector: &str,
@@ -69,3 +182,312 @@ pub async fn wait_for_chrome_xpath_selectors(
         },
     ))
 }
+
+/// Finds the elements matching any of the XPaths inside the elements matching the container
+/// selector, waiting for the container to appear. The containers are briefly marked with an
+/// attribute, so that each XPath can be evaluated relative to them.
+pub async fn wait_for_chrome_xpath_selectors_within(
+    page: &chromiumoxide::Page,
+    container: &str,
+    xpaths: &[String],
+    descriptor: &str,
+    timeout_secs: u64,
+) -> Result<Vec<chromiumoxide::element::Element>, ToolproofStepError> {
+    static MARKERS: AtomicUsize = AtomicUsize::new(0);
+
+    let start = std::time::Instant::now();
+    wait_for_chrome_element_selector(page, container, timeout_secs).await?;
+    let containers = page.find_elements(container).await.unwrap_or_default();
+
+    let marker = format!("toolproof-{}", MARKERS.fetch_add(1, Ordering::Relaxed));
+    let mark = format!(
+        "function() {{ this.setAttribute('data-toolproof-within', {}); }}",
+        serde_json::Value::String(marker.clone())
+    );
+    for container in &containers {
+        _ = container.call_js_fn(mark.as_str(), false).await;
+    }
+
+    let scoped = xpaths
+        .iter()
+        .map(|xpath| format!("//*[@data-toolproof-within='{marker}']{xpath}"))
+        .collect::<Vec<_>>()
+        .join(" | ");
+    let remaining = timeout_secs
+        .saturating_sub(start.elapsed().as_secs())
+        .max(1);
+    let found = wait_for_chrome_xpath_selectors(
+        page,
+        &scoped,
+        &format!("{descriptor} within {container}"),
+        remaining,
+    )
+    .await;
+
+    for container in &containers {
+        _ = container
+            .call_js_fn(
+                "function() { this.removeAttribute('data-toolproof-within'); }",
+                false,
+            )
+            .await;
+    }
+
+    found
+}
+
+/// Scrolls the element matching the selector into view and returns a point that can be clicked.
+/// If the element is detached from the DOM while we're doing this, a new element is selected.
+pub async fn chrome_selector_clickable_point(
+    page: &chromiumoxide::Page,
+    selector: &str,
+    timeout_secs: u64,
+) -> Result<Point, ToolproofStepError> {
+    loop {
+        let element = wait_for_chrome_element_selector(page, selector, timeout_secs).await?;
+
+        if let Err(e) = element.scroll_into_view().await {
+            match e {
+                // If the element was detached from the DOM after the time we selected it,
+                // we want to restart this section and select a new element.
+                CdpError::ScrollingFailed(msg) if msg.contains("detached") => continue,
+                _ => {
+                    return Err(ToolproofStepError::Assertion(
+                        ToolproofTestFailure::Custom {
+                            msg: format!("Element {selector} could not be scrolled into view: {e}"),
+                        },
+                    ))
+                }
+            }
+        }
+
+        match element.clickable_point().await {
+            Ok(c) => return Ok(c),
+            Err(e) => {
+                if let Ok(res) = element
+                    .call_js_fn("async function() { return this.isConnected; }", true)
+                    .await
+                {
+                    // If we can't find the center due to the element now being detached from the DOM,
+                    // we want to restart this section and select a new element.
+                    if matches!(res.result.value, Some(serde_json::Value::Bool(false))) {
+                        continue;
+                    }
+                }
+
+                return Err(ToolproofStepError::Assertion(
+                    ToolproofTestFailure::Custom {
+                        msg: format!("Could not find a clickable point for {selector}: {e}"),
+                    },
+                ));
+            }
+        }
+    }
+}
+
+/// Finds the frame owned by the iframe element matching the selector
+pub async fn wait_for_chrome_frame_selector(
+    page: &chromiumoxide::Page,
+    selector: &str,
+    timeout_secs: u64,
+) -> Result<FrameId, ToolproofStepError> {
+    let element = wait_for_chrome_element_selector(page, selector, timeout_secs).await?;
+
+    let description = page
+        .execute(
+            DescribeNodeParams::builder()
+                .backend_node_id(element.backend_node_id)
+                .build(),
+        )
+        .await
+        .map_err(|e| ToolproofStepError::Internal(e.into()))?;
+
+    description.result.node.frame_id.ok_or_else(|| {
+        ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+            msg: format!("Element {selector} is not a frame"),
+        })
+    })
+}
+
+/// Waits for the frame to have a JavaScript context that we can evaluate within.
+/// Frames from another origin run in a separate process, and never get one on this page.
+pub async fn wait_for_chrome_frame_context(
+    page: &chromiumoxide::Page,
+    frame_id: &FrameId,
+    frame_selector: &str,
+    timeout_secs: u64,
+) -> Result<ExecutionContextId, ToolproofStepError> {
+    let start = std::time::Instant::now();
+    while start.elapsed().as_secs() < timeout_secs {
+        if let Ok(Some(context)) = page.frame_execution_context(frame_id.clone()).await {
+            return Ok(context);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    Err(ToolproofStepError::Assertion(
+        ToolproofTestFailure::Custom {
+            msg: format!(
+                "Frame {frame_selector} did not load within {timeout_secs}s (frames from another origin are not supported)"
+            ),
+        },
+    ))
+}
+
+/// Runs a JavaScript function declaration within the given frame context
+pub async fn chrome_evaluate_in_context(
+    page: &chromiumoxide::Page,
+    context: ExecutionContextId,
+    function_declaration: String,
+) -> Result<Option<serde_json::Value>, ToolproofStepError> {
+    let params = CallFunctionOnParams::builder()
+        .function_declaration(function_declaration)
+        .execution_context_id(context)
+        .await_promise(true)
+        .return_by_value(true)
+        .build()
+        .map_err(|e| ToolproofStepError::Internal(ToolproofInternalError::Custom { msg: e }))?;
+
+    let res = page
+        .evaluate_function(params)
+        .await
+        .map_err(|inner| ToolproofStepError::Internal(inner.into()))?;
+
+    Ok(res.object().value.clone())
+}
+
+/// Scrolls the element matching the selector within a frame into view, and returns
+/// a point that can be clicked, relative to the page's viewport.
+pub async fn chrome_frame_selector_clickable_point(
+    page: &chromiumoxide::Page,
+    frame_id: &FrameId,
+    frame_selector: &str,
+    selector: &str,
+    timeout_secs: u64,
+) -> Result<Point, ToolproofStepError> {
+    let context =
+        wait_for_chrome_frame_context(page, frame_id, frame_selector, timeout_secs).await?;
+
+    let inner_point = chrome_evaluate_in_context(
+        page,
+        context,
+        format!(
+            r#"async function() {{
+                const selector = {};
+                const deadline = Date.now() + {};
+                let el = document.querySelector(selector);
+                while (!el && Date.now() < deadline) {{
+                    await new Promise((r) => setTimeout(r, 100));
+                    el = document.querySelector(selector);
+                }}
+                if (!el) return null;
+                el.scrollIntoView({{ block: "center", inline: "center" }});
+                const rect = el.getBoundingClientRect();
+                return {{ x: rect.x + rect.width / 2, y: rect.y + rect.height / 2 }};
+            }}"#,
+            serde_json::Value::String(selector.to_string()),
+            timeout_secs * 1000
+        ),
+    )
+    .await?;
+
+    let Some(inner_point) = inner_point.as_ref().and_then(json_point) else {
+        return Err(ToolproofStepError::Assertion(
+            ToolproofTestFailure::Custom {
+                msg: format!(
+                    "Element {selector} could not be found within the frame {frame_selector} within {timeout_secs}s"
+                ),
+            },
+        ));
+    };
+
+    // Points within the frame are relative to the frame's own viewport,
+    // so we offset them by where the frame's content sits on the page
+    let frame_element =
+        wait_for_chrome_element_selector(page, frame_selector, timeout_secs).await?;
+    let frame_offset = frame_element
+        .call_js_fn(
+            "function() { const r = this.getBoundingClientRect(); return { x: r.x + this.clientLeft, y: r.y + this.clientTop }; }",
+            false,
+        )
+        .await
+        .map_err(|e| ToolproofStepError::Internal(e.into()))?;
+
+    let Some(frame_offset) = frame_offset.result.value.as_ref().and_then(json_point) else {
+        return Err(ToolproofStepError::Assertion(
+            ToolproofTestFailure::Custom {
+                msg: format!("Could not find the position of the frame {frame_selector}"),
+            },
+        ));
+    };
+
+    Ok(Point {
+        x: frame_offset.x + inner_point.x,
+        y: frame_offset.y + inner_point.y,
+    })
+}
+
+fn json_point(value: &serde_json::Value) -> Option<Point> {
+    Some(Point {
+        x: value.get("x")?.as_f64()?,
+        y: value.get("y")?.as_f64()?,
+    })
+}
+
+fn ax_value_string(value: &Option<AxValue>) -> Option<String> {
+    let value = value.as_ref()?.value.as_ref()?;
+    let value = match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    (!value.is_empty()).then_some(value)
+}
+
+fn simplify_ax_node(id: &str, nodes: &HashMap<&str, &AxNode>) -> Vec<serde_json::Value> {
+    let Some(node) = nodes.get(id) else {
+        return vec![];
+    };
+
+    let children = node
+        .child_ids
+        .iter()
+        .flatten()
+        .flat_map(|child| simplify_ax_node(child.inner(), nodes))
+        .collect::<Vec<_>>();
+
+    let role = ax_value_string(&node.role);
+    // Nodes that aren't exposed to assistive technology are skipped, keeping their children
+    let Some(role) = role.filter(|r| !node.ignored && r != "none" && r != "InlineTextBox") else {
+        return children;
+    };
+
+    let mut simplified = serde_json::Map::new();
+    simplified.insert("role".to_string(), role.into());
+    if let Some(name) = ax_value_string(&node.name) {
+        simplified.insert("name".to_string(), name.into());
+    }
+    if let Some(value) = ax_value_string(&node.value) {
+        simplified.insert("value".to_string(), value.into());
+    }
+    if !children.is_empty() {
+        simplified.insert("children".to_string(), children.into());
+    }
+
+    vec![serde_json::Value::Object(simplified)]
+}
+
+/// Reduces Chrome's full accessibility tree down to nested role/name/value objects
+pub fn simplify_ax_tree(nodes: &[AxNode]) -> serde_json::Value {
+    let by_id = nodes
+        .iter()
+        .map(|node| (node.node_id.inner().as_str(), node))
+        .collect::<HashMap<_, _>>();
+
+    let Some(root) = nodes.iter().find(|node| node.parent_id.is_none()) else {
+        return serde_json::Value::Null;
+    };
+
+    match simplify_ax_node(root.node_id.inner(), &by_id).as_slice() {
+        [single] => single.clone(),
+        many => serde_json::Value::Array(many.to_vec()),
+    }
+}
@@ -0,0 +1,72 @@
+// Finds text whose contrast with its background is below WCAG AA.
+// Inserted into the harness, so this runs inside an async function.
+
+const parseColor = (color) => {
+  const [r = 0, g = 0, b = 0, a = 1] = (color.match(/[\d.]+/g) || []).map(Number);
+  return { r, g, b, a };
+};
+
+const blend = (top, bottom) => ({
+  r: top.r * top.a + bottom.r * (1 - top.a),
+  g: top.g * top.a + bottom.g * (1 - top.a),
+  b: top.b * top.a + bottom.b * (1 - top.a),
+  a: 1,
+});
+
+const backgroundOf = (el) => {
+  const layers = [];
+  for (let node = el; node; node = node.parentElement) {
+    const bg = parseColor(getComputedStyle(node).backgroundColor);
+    if (bg.a > 0) layers.push(bg);
+    if (bg.a >= 1) break;
+  }
+  return layers.reduceRight((under, layer) => blend(layer, under), { r: 255, g: 255, b: 255, a: 1 });
+};
+
+const luminance = ({ r, g, b }) => {
+  const [lr, lg, lb] = [r, g, b].map((c) => {
+    c /= 255;
+    return c <= 0.03928 ? c / 12.92 : Math.pow((c + 0.055) / 1.055, 2.4);
+  });
+  return 0.2126 * lr + 0.7152 * lg + 0.0722 * lb;
+};
+
+const toCss = ({ r, g, b }) => `rgb(${Math.round(r)}, ${Math.round(g)}, ${Math.round(b)})`;
+
+const issues = [];
+const walker = document.createTreeWalker(document.body, NodeFilter.SHOW_TEXT);
+const seen = new Set();
+
+while (walker.nextNode()) {
+  const text = walker.currentNode.textContent.trim();
+  const el = walker.currentNode.parentElement;
+  if (!text || !el || seen.has(el)) continue;
+  seen.add(el);
+
+  const style = getComputedStyle(el);
+  const rect = el.getBoundingClientRect();
+  if (style.display === "none" || style.visibility === "hidden" || !rect.width || !rect.height) {
+    continue;
+  }
+
+  const background = backgroundOf(el);
+  const foreground = blend(parseColor(style.color), background);
+  const [light, dark] = [luminance(foreground), luminance(background)].sort((a, b) => b - a);
+  const ratio = (light + 0.05) / (dark + 0.05);
+
+  const size = parseFloat(style.fontSize);
+  const large = size >= 24 || (size >= 18.66 && Number(style.fontWeight) >= 700);
+  const required = large ? 3 : 4.5;
+
+  if (ratio < required) {
+    issues.push({
+      text: text.slice(0, 80),
+      ratio: Math.round(ratio * 100) / 100,
+      required,
+      foreground: toCss(foreground),
+      background: toCss(background),
+    });
+  }
+}
+
+return issues;
@@ -0,0 +1,50 @@
+use image::{ImageBuffer, Rgba, RgbaImage};
+
+/// How far apart two channel values can be before a pixel counts as different,
+/// so that slight antialiasing changes don't fail a comparison
+const CHANNEL_THRESHOLD: u8 = 16;
+
+pub struct ImageDiff {
+    /// The fraction of pixels (0 to 1) that differ
+    pub difference: f64,
+    /// The baseline image faded out, with differing pixels highlighted in red
+    pub diff_image: RgbaImage,
+}
+
+/// Compares two images pixel by pixel, returning `None` if their dimensions differ
+pub fn diff_images(baseline: &RgbaImage, actual: &RgbaImage) -> Option<ImageDiff> {
+    if baseline.dimensions() != actual.dimensions() {
+        return None;
+    }
+
+    let (width, height) = baseline.dimensions();
+    let mut differing = 0usize;
+
+    let diff_image = ImageBuffer::from_fn(width, height, |x, y| {
+        let expected = baseline.get_pixel(x, y);
+        let found = actual.get_pixel(x, y);
+
+        let differs = expected
+            .0
+            .iter()
+            .zip(found.0.iter())
+            .any(|(a, b)| a.abs_diff(*b) > CHANNEL_THRESHOLD);
+
+        if differs {
+            differing += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            let [r, g, b, _] = expected.0;
+            let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+            let faded = (255 - (255 - luma) / 4) as u8;
+            Rgba([faded, faded, faded, 255])
+        }
+    });
+
+    let total = (width as usize * height as usize).max(1);
+
+    Some(ImageDiff {
+        difference: differing as f64 / total as f64,
+        diff_image,
+    })
+}
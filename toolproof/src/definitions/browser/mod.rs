@@ -1,24 +1,45 @@
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
-use chromiumoxide::cdp::browser_protocol::page::CaptureScreenshotParams;
-use chromiumoxide::cdp::browser_protocol::browser::BrowserContextId;
+use chromiumoxide::cdp::browser_protocol::accessibility::GetFullAxTreeParams;
+use chromiumoxide::cdp::browser_protocol::browser::{
+    BrowserContextId, GrantPermissionsParams, PermissionType,
+};
+use chromiumoxide::cdp::browser_protocol::dom::SetFileInputFilesParams;
+use chromiumoxide::cdp::browser_protocol::emulation::SetGeolocationOverrideParams;
+use chromiumoxide::cdp::browser_protocol::input::{
+    DispatchMouseEventParams, DispatchMouseEventType, MouseButton,
+};
+use chromiumoxide::cdp::browser_protocol::network::{
+    EmulateNetworkConditionsParams, SetUserAgentOverrideParams,
+};
+use chromiumoxide::cdp::browser_protocol::page::{
+    CaptureScreenshotFormat, CaptureScreenshotParams, EventFrameNavigated,
+    EventNavigatedWithinDocument, FrameId, GetNavigationHistoryParams, NavigateParams,
+    NavigateToHistoryEntryParams, Viewport as ClipViewport,
+};
 use chromiumoxide::cdp::browser_protocol::target::{
     CreateBrowserContextParams, CreateTargetParams,
 };
 use chromiumoxide::error::CdpError;
 use chromiumoxide::handler::viewport::Viewport;
+use chromiumoxide::layout::Point;
 use chromiumoxide::page::ScreenshotParams;
 use futures::future::join_all;
 use futures::StreamExt;
+use schematic::color::owo::OwoColorize;
 use tempfile::tempdir;
 use tokio::task::JoinHandle;
 
+use crate::certificate::local_certificate;
 use crate::civilization::Civilization;
 use crate::errors::{
     ToolproofInputError, ToolproofInternalError, ToolproofStepError, ToolproofTestFailure,
 };
+use crate::logging::log_eprintln;
 use crate::options::ToolproofParams;
 
 use super::{SegmentArgs, ToolproofInstruction, ToolproofRetriever};
@@ -27,9 +48,23 @@ use chromiumoxide::browser::{Browser, BrowserConfig};
 use pagebrowse::{PagebrowseBuilder, Pagebrowser, PagebrowserWindow};
 
 mod browser_specific;
+mod image_diff;
+
+use browser_specific::NetworkEvent;
+
+/// How many intermediate mouse movements to make when dragging between elements
+const DRAG_STEPS: usize = 10;
+
+/// How long the network must be quiet before we consider it idle
+const NETWORK_IDLE_DURATION: Duration = Duration::from_millis(500);
+
+/// How long a request can stay open before it no longer stops the network from being idle,
+/// such as a long poll that only responds when something changes
+const NETWORK_IDLE_MAX_REQUEST_DURATION: Duration = Duration::from_secs(10);
 
 const HARNESS: &'static str = include_str!("./harness.js");
 const INIT_SCRIPT: &'static str = include_str!("./init.js");
+const CONTRAST_SCRIPT: &'static str = include_str!("./contrast.js");
 
 fn harnessed(js: String) -> String {
     HARNESS.replace("// insert_toolproof_inner_js", &js)
@@ -42,7 +77,7 @@ fn init_script(timeout_secs: u64) -> String {
 /// We want selector steps to timeout before the step itself does,
 /// since it provides a better error. This makes that more likely.
 fn auto_selector_timeout(civ: &Civilization) -> u64 {
-    civ.universe.ctx.params.timeout.saturating_sub(2).max(1)
+    civ.step_timeout.saturating_sub(2).max(1)
 }
 
 fn escape_xpath_string(s: &str) -> String {
@@ -55,43 +90,109 @@ fn escape_xpath_string(s: &str) -> String {
     }
 }
 
+/// Browser contexts that finished tests have handed back, for the next test to reuse
+pub type BrowserContextPool = Arc<Mutex<Vec<BrowserContextId>>>;
+
 pub enum BrowserTester {
     Pagebrowse(Arc<Pagebrowser>),
     Chrome {
         browser: Arc<Browser>,
         browser_timeout: u64,
+        /// Only set if the `reuse_browser_context` option is enabled
+        context_pool: Option<BrowserContextPool>,
         event_thread: Arc<JoinHandle<Result<(), std::io::Error>>>,
     },
 }
 
-async fn try_launch_browser(mut max: usize, visible: bool) -> (Browser, chromiumoxide::Handler) {
-    let mut launch = Err(CdpError::NotFound);
-    while launch.is_err() && max > 0 {
-        max -= 1;
+/// Why the browser couldn't be launched, which is shown along with how to fix it
+enum BrowserLaunchError {
+    /// No Chrome executable could be found, or it couldn't be run
+    NotFound(String),
+    /// Chrome was found, but exited or stopped responding while launching
+    Crashed(String),
+}
+
+impl From<CdpError> for BrowserLaunchError {
+    fn from(e: CdpError) -> Self {
+        match &e {
+            CdpError::Io(inner) | CdpError::LaunchIo(inner, _)
+                if matches!(
+                    inner.kind(),
+                    std::io::ErrorKind::NotFound | std::io::ErrorKind::PermissionDenied
+                ) =>
+            {
+                BrowserLaunchError::NotFound(e.to_string())
+            }
+            _ => BrowserLaunchError::Crashed(e.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for BrowserLaunchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrowserLaunchError::NotFound(e) => write!(
+                f,
+                "Failed to launch browser, as Chrome could not be found ({e}).\n\
+                Install Google Chrome or Chromium, or set the CHROME environment variable to the path of a Chrome executable.\n\
+                Alternatively, run browser tests with `--browser pagebrowse`."
+            ),
+            BrowserLaunchError::Crashed(e) => write!(
+                f,
+                "Failed to launch browser, as Chrome was found but did not start ({e}).\n\
+                Check that Chrome can be started from a terminal on this machine, \
+                as it may be missing system libraries in some CI environments.\n\
+                Alternatively, run browser tests with `--browser pagebrowse`."
+            ),
+        }
+    }
+}
+
+async fn try_launch_browser(
+    max: usize,
+    visible: bool,
+) -> Result<(Browser, chromiumoxide::Handler), BrowserLaunchError> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
         let mut builder = BrowserConfig::builder();
         if visible {
             builder = builder.with_head();
         }
-        launch = Browser::launch(
-            builder
-                .user_data_dir(tempdir().expect("testing on a system with a temp dir"))
-                .viewport(Some(Viewport {
-                    width: 1600,
-                    height: 900,
-                    device_scale_factor: Some(2.0),
-                    emulating_mobile: false,
-                    is_landscape: true,
-                    has_touch: false,
-                }))
-                .build()
-                .unwrap(),
-        )
-        .await;
-    }
-    match launch {
-        Ok(res) => res,
-        Err(e) => {
-            panic!("Failed to launch browser due to error: {e}");
+        let user_data_dir = tempdir().map_err(|e| {
+            BrowserLaunchError::Crashed(format!("could not create a user data directory: {e}"))
+        })?;
+        // Allows testing sites served by the `I serve the directory {dir} over https` step,
+        // trusting only the certificate generated for them rather than ignoring every
+        // certificate error. Without it, https sites are left to fail as they normally would.
+        if let Ok(certificate) = local_certificate() {
+            builder = builder.arg(format!(
+                "--ignore-certificate-errors-spki-list={}",
+                certificate.spki_sha256
+            ));
+        }
+        let config = builder
+            .user_data_dir(user_data_dir)
+            .viewport(Some(Viewport {
+                width: 1600,
+                height: 900,
+                device_scale_factor: Some(2.0),
+                emulating_mobile: false,
+                is_landscape: true,
+                has_touch: false,
+            }))
+            .build()
+            .map_err(BrowserLaunchError::NotFound)?;
+
+        match Browser::launch(config)
+            .await
+            .map_err(BrowserLaunchError::from)
+        {
+            Ok(launched) => return Ok(launched),
+            // Retrying won't help if there's nothing to launch
+            Err(e @ BrowserLaunchError::NotFound(_)) => return Err(e),
+            Err(e) if attempts >= max => return Err(e),
+            Err(_) => {}
         }
     }
 }
@@ -101,21 +202,37 @@ enum InteractionType {
     Hover,
 }
 
+/// How the text given to a text interaction is compared to the text of each element
+enum TextMatch {
+    /// The element's text contains the given text
+    Contains,
+    /// The element's text is the given text, ignoring case and surrounding whitespace
+    Exact,
+}
+
 impl BrowserTester {
-    async fn initialize(params: &ToolproofParams) -> Self {
+    async fn initialize(params: &ToolproofParams) -> Result<Self, String> {
         match params.browser {
             crate::options::ToolproofBrowserImpl::Chrome => {
                 let visible = params.debugger;
-                let (browser, mut handler) = try_launch_browser(3, visible).await;
+                let (browser, mut handler) = try_launch_browser(3, visible).await.map_err(|e| {
+                    // Tests only show this as a step error, so the guidance is also
+                    // printed once up front where it is easier to spot
+                    log_eprintln!("{}", e.to_string().red());
+                    e.to_string()
+                })?;
 
-                BrowserTester::Chrome {
+                Ok(BrowserTester::Chrome {
                     browser: Arc::new(browser),
                     browser_timeout: params.browser_timeout,
+                    context_pool: params
+                        .reuse_browser_context
+                        .then(|| Arc::new(Mutex::new(vec![]))),
                     event_thread: Arc::new(tokio::task::spawn(async move {
                         while handler.next().await.is_some() {}
                         Ok::<(), std::io::Error>(())
                     })),
-                }
+                })
             }
             crate::options::ToolproofBrowserImpl::Pagebrowse => {
                 let pagebrowser = PagebrowseBuilder::new(params.concurrency)
@@ -127,32 +244,59 @@ impl BrowserTester {
                     .init_script(init_script(params.browser_timeout))
                     .build()
                     .await
-                    .expect("Can't build the pagebrowser");
+                    .map_err(|e| format!("Failed to launch the pagebrowser due to error: {e}"))?;
 
-                BrowserTester::Pagebrowse(Arc::new(pagebrowser))
+                Ok(BrowserTester::Pagebrowse(Arc::new(pagebrowser)))
             }
         }
     }
 
-    async fn get_window(&self) -> BrowserWindow {
+    async fn get_window(&self) -> Result<BrowserWindow, ToolproofStepError> {
         match self {
             BrowserTester::Pagebrowse(pb) => {
-                BrowserWindow::Pagebrowse(pb.get_window().await.unwrap())
+                Ok(BrowserWindow::Pagebrowse(pb.get_window().await.unwrap()))
             }
             BrowserTester::Chrome {
                 browser,
                 browser_timeout,
+                context_pool,
                 ..
             } => {
-                let context_id = browser
-                    .create_browser_context(CreateBrowserContextParams {
-                        dispose_on_detach: Some(true),
-                        proxy_server: None,
-                        proxy_bypass_list: None,
-                        origins_with_universal_network_access: None,
-                    })
+                let idle_context = context_pool
+                    .as_ref()
+                    .and_then(|pool| pool.lock().unwrap().pop());
+                let context_id = match idle_context {
+                    Some(context_id) => context_id,
+                    None => browser
+                        .create_browser_context(CreateBrowserContextParams {
+                            dispose_on_detach: Some(true),
+                            proxy_server: None,
+                            proxy_bypass_list: None,
+                            origins_with_universal_network_access: None,
+                        })
+                        .await
+                        .unwrap(),
+                };
+                // Lets tests read what the page copies, e.g. from a "copy link" button
+                browser
+                    .execute(
+                        GrantPermissionsParams::builder()
+                            .permissions([
+                                PermissionType::ClipboardReadWrite,
+                                PermissionType::ClipboardSanitizedWrite,
+                            ])
+                            .browser_context_id(context_id.clone())
+                            .build()
+                            .unwrap(),
+                    )
                     .await
-                    .unwrap();
+                    .map_err(|e| {
+                        ToolproofStepError::Internal(ToolproofInternalError::Custom {
+                            msg: format!(
+                                "Could not grant clipboard permissions to the browser: {e}"
+                            ),
+                        })
+                    })?;
                 let page = browser
                     .new_page(CreateTargetParams {
                         url: "about:blank".to_string(),
@@ -173,11 +317,22 @@ impl BrowserTester {
                 page.evaluate_on_new_document(init_script(*browser_timeout))
                     .await
                     .expect("Could not set initialization js");
-                BrowserWindow::Chrome {
+                let failed_requests = Arc::new(Mutex::new(vec![]));
+                browser_specific::track_chrome_failed_requests(&page, Arc::clone(&failed_requests))
+                    .await
+                    .map_err(|e| {
+                        ToolproofStepError::Internal(ToolproofInternalError::Custom {
+                            msg: format!("Could not listen for network events in the browser: {e}"),
+                        })
+                    })?;
+                Ok(BrowserWindow::Chrome {
                     page,
                     context_id,
                     browser: Arc::clone(browser),
-                }
+                    frame: None,
+                    failed_requests,
+                    context_pool: context_pool.clone(),
+                })
             }
         }
     }
@@ -188,19 +343,218 @@ pub enum BrowserWindow {
         page: chromiumoxide::Page,
         context_id: BrowserContextId,
         browser: Arc<Browser>,
+        /// The iframe that scripts and selectors currently target, if not the main frame
+        frame: Option<ChromeFrame>,
+        /// Requests from this window that returned an error status or failed to load
+        failed_requests: Arc<Mutex<Vec<serde_json::Value>>>,
+        /// Where to hand the browser context back to when the test finishes, if it is reused
+        context_pool: Option<BrowserContextPool>,
     },
     Pagebrowse(PagebrowserWindow),
 }
 
+pub struct ChromeFrame {
+    selector: String,
+    id: FrameId,
+    timeout_secs: u64,
+}
+
+/// Browser settings that a test has changed, which are applied to every page it loads
+pub struct BrowserEmulation {
+    pub user_agent: Option<String>,
+    /// The `(latitude, longitude)` that pages are told they are at
+    pub geolocation: Option<(f64, f64)>,
+    pub network: Option<NetworkConditions>,
+}
+
+impl BrowserEmulation {
+    pub fn new(params: &ToolproofParams) -> Self {
+        Self {
+            user_agent: params.user_agent.clone(),
+            geolocation: None,
+            network: None,
+        }
+    }
+
+    fn is_set(&self) -> bool {
+        self.user_agent.is_some() || self.geolocation.is_some() || self.network.is_some()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct NetworkConditions {
+    offline: bool,
+    latency_ms: f64,
+    /// Bytes per second, or `-1` for no limit
+    download_throughput: f64,
+    /// Bytes per second, or `-1` for no limit
+    upload_throughput: f64,
+}
+
+impl NetworkConditions {
+    const ONLINE: Self = Self {
+        offline: false,
+        latency_ms: 0.0,
+        download_throughput: -1.0,
+        upload_throughput: -1.0,
+    };
+
+    const OFFLINE: Self = Self {
+        offline: true,
+        ..Self::ONLINE
+    };
+
+    /// Names of the presets for [`NetworkConditions::preset`], which match the Chrome DevTools presets
+    const PRESETS: [&'static str; 4] = ["slow 3g", "fast 3g", "slow 4g", "fast 4g"];
+
+    fn preset(name: &str) -> Option<Self> {
+        let (latency_ms, download_throughput, upload_throughput) =
+            match name.trim().to_lowercase().as_str() {
+                "slow 3g" => (2000.0, 50_000.0, 50_000.0),
+                "fast 3g" | "slow 4g" => (562.5, 180_000.0, 84_375.0),
+                "fast 4g" => (165.0, 1_012_500.0, 168_750.0),
+                _ => return None,
+            };
+
+        Some(Self {
+            offline: false,
+            latency_ms,
+            download_throughput,
+            upload_throughput,
+        })
+    }
+}
+
 impl BrowserWindow {
+    /// Closes the page, and hands its browser context back to the pool or disposes of it
+    pub async fn close(self) {
+        if let BrowserWindow::Chrome {
+            page,
+            context_id,
+            browser,
+            context_pool,
+            ..
+        } = self
+        {
+            match tokio::time::timeout(Duration::from_secs(5), async {
+                if let Err(e) = page.close().await {
+                    eprintln!("[toolproof] Warning: Failed to close browser window: {e}");
+                }
+                if let Some(pool) = context_pool {
+                    pool.lock().unwrap().push(context_id);
+                } else if let Err(e) = browser.dispose_browser_context(context_id).await {
+                    eprintln!("[toolproof] Warning: Failed to dispose browser context: {e}");
+                }
+            })
+            .await
+            {
+                Ok(()) => {}
+                Err(_) => {
+                    eprintln!("[toolproof] Warning: Timed out cleaning up browser window");
+                }
+            }
+        }
+    }
+
+    async fn switch_to_frame(
+        &mut self,
+        selector: &str,
+        timeout_secs: u64,
+    ) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, frame, .. } => {
+                // Frames are always looked up from the main page, rather than within the current frame
+                let id =
+                    browser_specific::wait_for_chrome_frame_selector(page, selector, timeout_secs)
+                        .await?;
+                browser_specific::wait_for_chrome_frame_context(page, &id, selector, timeout_secs)
+                    .await?;
+
+                *frame = Some(ChromeFrame {
+                    selector: selector.to_string(),
+                    id,
+                    timeout_secs,
+                });
+
+                Ok(())
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Frames not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
+    fn switch_to_main_frame(&mut self) {
+        if let BrowserWindow::Chrome { frame, .. } = self {
+            *frame = None;
+        }
+    }
+
+    /// Errors for interactions that can only find elements in the main frame
+    fn require_main_frame(&self, action: &str) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome {
+                frame: Some(frame), ..
+            } => Err(ToolproofStepError::External(
+                ToolproofInputError::StepRequirementsNotMet {
+                    reason: format!(
+                        "{action} are not supported within frames, but the frame {} is selected. Switch to the main frame first",
+                        frame.selector
+                    ),
+                },
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    async fn selector_clickable_point(
+        &self,
+        selector: &str,
+        timeout_secs: u64,
+    ) -> Result<Point, ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome {
+                page, frame: None, ..
+            } => {
+                browser_specific::chrome_selector_clickable_point(page, selector, timeout_secs)
+                    .await
+            }
+            BrowserWindow::Chrome {
+                page,
+                frame: Some(frame),
+                ..
+            } => {
+                browser_specific::chrome_frame_selector_clickable_point(
+                    page,
+                    &frame.id,
+                    &frame.selector,
+                    selector,
+                    timeout_secs,
+                )
+                .await
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Clicks not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
     async fn navigate(&self, url: String, wait_for_load: bool) -> Result<(), ToolproofStepError> {
         match self {
             BrowserWindow::Chrome { page, .. } => {
-                // TODO: This is implicitly always wait_for_load: true
-                page.goto(url)
-                    .await
-                    .map(|_| ())
-                    .map_err(|inner| ToolproofStepError::Internal(inner.into()))
+                let res = if wait_for_load {
+                    page.goto(url).await.map(|_| ())
+                } else {
+                    // Sending Page.navigate directly returns once navigation has started,
+                    // rather than waiting for the load event like goto does
+                    page.execute(NavigateParams::new(url)).await.map(|_| ())
+                };
+
+                res.map_err(|inner| ToolproofStepError::Internal(inner.into()))
             }
             BrowserWindow::Pagebrowse(window) => window
                 .navigate(url, wait_for_load)
@@ -209,14 +563,284 @@ impl BrowserWindow {
         }
     }
 
+    /// Navigates to the url, then waits until no requests have been in flight
+    /// for [`NETWORK_IDLE_DURATION`]. Streaming requests, and requests open for longer than
+    /// [`NETWORK_IDLE_MAX_REQUEST_DURATION`], are left out so that open connections don't
+    /// stop the page from becoming idle. Relies on the step timeout if the network never settles.
+    async fn navigate_and_wait_for_network_idle(
+        &self,
+        url: String,
+    ) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, .. } => {
+                let map_err = |inner: CdpError| ToolproofStepError::Internal(inner.into());
+
+                let mut events = browser_specific::chrome_network_events(page)
+                    .await
+                    .map_err(map_err)?;
+
+                page.goto(url).await.map_err(map_err)?;
+
+                // When each open request was sent
+                let mut in_flight = HashMap::new();
+                // A request can end before it is seen being sent,
+                // so ended requests are kept to not count them as in flight afterwards
+                let mut ended = HashSet::new();
+                loop {
+                    tokio::select! {
+                        Some(event) = events.next() => match event {
+                            NetworkEvent::Sent(event) => {
+                                if !ended.contains(&event.request_id)
+                                    && !browser_specific::is_streaming_request(&event)
+                                {
+                                    in_flight
+                                        .entry(event.request_id.clone())
+                                        .or_insert_with(Instant::now);
+                                }
+                            }
+                            NetworkEvent::Finished(event) => {
+                                in_flight.remove(&event.request_id);
+                                ended.insert(event.request_id.clone());
+                            }
+                            NetworkEvent::Failed(event) => {
+                                in_flight.remove(&event.request_id);
+                                ended.insert(event.request_id.clone());
+                            }
+                            NetworkEvent::Response(_) => {}
+                        },
+                        _ = tokio::time::sleep(NETWORK_IDLE_DURATION) => {
+                            in_flight.retain(|_, sent_at: &mut Instant| {
+                                sent_at.elapsed() < NETWORK_IDLE_MAX_REQUEST_DURATION
+                            });
+                            if in_flight.is_empty() {
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Waiting for network idle not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
+    async fn apply_emulation(
+        &self,
+        emulation: &BrowserEmulation,
+    ) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome {
+                page,
+                context_id,
+                browser,
+                ..
+            } => {
+                let map_err = |inner: CdpError| ToolproofStepError::Internal(inner.into());
+
+                if let Some(user_agent) = &emulation.user_agent {
+                    page.set_user_agent(SetUserAgentOverrideParams::new(user_agent.clone()))
+                        .await
+                        .map_err(map_err)?;
+                }
+
+                if let Some((latitude, longitude)) = emulation.geolocation {
+                    // Granting permissions replaces the existing grants,
+                    // so the clipboard permissions from opening the window are kept here
+                    browser
+                        .execute(
+                            GrantPermissionsParams::builder()
+                                .permissions([
+                                    PermissionType::ClipboardReadWrite,
+                                    PermissionType::ClipboardSanitizedWrite,
+                                    PermissionType::Geolocation,
+                                ])
+                                .browser_context_id(context_id.clone())
+                                .build()
+                                .unwrap(),
+                        )
+                        .await
+                        .map_err(map_err)?;
+                    page.execute(
+                        SetGeolocationOverrideParams::builder()
+                            .latitude(latitude)
+                            .longitude(longitude)
+                            .accuracy(1.0)
+                            .build(),
+                    )
+                    .await
+                    .map_err(map_err)?;
+                }
+
+                if let Some(conditions) = &emulation.network {
+                    self.emulate_network(conditions).await?;
+                }
+
+                Ok(())
+            }
+            BrowserWindow::Pagebrowse(_) => {
+                if emulation.is_set() {
+                    return Err(ToolproofStepError::Internal(
+                        ToolproofInternalError::Custom {
+                            msg: "Changing the user agent, location, or network not yet implemented for Pagebrowse"
+                                .to_string(),
+                        },
+                    ));
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    async fn emulate_network(
+        &self,
+        conditions: &NetworkConditions,
+    ) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, .. } => page
+                .execute(EmulateNetworkConditionsParams::new(
+                    conditions.offline,
+                    conditions.latency_ms,
+                    conditions.download_throughput,
+                    conditions.upload_throughput,
+                ))
+                .await
+                .map(|_| ())
+                .map_err(|inner| ToolproofStepError::Internal(inner.into())),
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Network emulation not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
+    fn failed_requests(&self) -> Result<serde_json::Value, ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome {
+                failed_requests, ..
+            } => Ok(serde_json::Value::Array(
+                failed_requests.lock().unwrap().clone(),
+            )),
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Tracking failed requests not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
+    /// Reloads the current page, which returns scripts and selectors to the main frame
+    async fn reload(&mut self) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, frame, .. } => {
+                page.reload()
+                    .await
+                    .map_err(|inner| ToolproofStepError::Internal(inner.into()))?;
+                *frame = None;
+
+                Ok(())
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Reloading not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
+    /// Moves through the page's history by `offset` entries, such as `-1` to go back
+    async fn navigate_history(&mut self, offset: i64) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, frame, .. } => {
+                let map_err = |inner: CdpError| ToolproofStepError::Internal(inner.into());
+
+                let history = page
+                    .execute(GetNavigationHistoryParams::default())
+                    .await
+                    .map_err(map_err)?
+                    .result;
+                let entry = usize::try_from(history.current_index + offset)
+                    .ok()
+                    .and_then(|index| history.entries.get(index));
+                let Some(entry) = entry else {
+                    let direction = if offset < 0 { "back" } else { "forward" };
+                    return Err(ToolproofStepError::External(
+                        ToolproofInputError::StepRequirementsNotMet {
+                            reason: format!(
+                                "there is no page to go {direction} to in the browser history"
+                            ),
+                        },
+                    ));
+                };
+
+                let main_frame = page.mainframe().await.map_err(map_err)?;
+                let mut navigated = page
+                    .event_listener::<EventFrameNavigated>()
+                    .await
+                    .map_err(map_err)?;
+                let mut navigated_within_document = page
+                    .event_listener::<EventNavigatedWithinDocument>()
+                    .await
+                    .map_err(map_err)?;
+
+                page.execute(NavigateToHistoryEntryParams::new(entry.id))
+                    .await
+                    .map_err(map_err)?;
+
+                // Entries that the page pushed itself (e.g. in a SPA) stay on the same
+                // document, so never load and keep any selected frame
+                loop {
+                    tokio::select! {
+                        Some(event) = navigated.next() => {
+                            if event.frame.parent_id.is_none() {
+                                page.wait_for_navigation().await.map_err(map_err)?;
+                                *frame = None;
+                                return Ok(());
+                            }
+                        }
+                        Some(event) = navigated_within_document.next() => {
+                            if main_frame.as_ref() == Some(&event.frame_id) {
+                                return Ok(());
+                            }
+                        }
+                        else => return Ok(()),
+                    }
+                }
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "History navigation not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
     async fn evaluate_script(
         &self,
         script: String,
     ) -> Result<Option<serde_json::Value>, ToolproofStepError> {
         match self {
-            BrowserWindow::Chrome { page, .. } => {
+            BrowserWindow::Chrome { page, frame, .. } => {
+                let function = format!("async function() {{{}}}", harnessed(script));
+
+                if let Some(frame) = frame {
+                    let context = browser_specific::wait_for_chrome_frame_context(
+                        page,
+                        &frame.id,
+                        &frame.selector,
+                        frame.timeout_secs,
+                    )
+                    .await?;
+                    return browser_specific::chrome_evaluate_in_context(page, context, function)
+                        .await;
+                }
+
                 let res = page
-                    .evaluate_function(format!("async function() {{{}}}", harnessed(script)))
+                    .evaluate_function(function)
                     .await
                     .map_err(|inner| ToolproofStepError::Internal(inner.into()))?;
 
@@ -257,29 +881,19 @@ impl BrowserWindow {
         }
     }
 
-    async fn screenshot_element(
-        &self,
-        selector: &str,
-        filepath: PathBuf,
-        timeout_secs: u64,
-    ) -> Result<(), ToolproofStepError> {
+    async fn screenshot_page_png(&self) -> Result<Vec<u8>, ToolproofStepError> {
         match self {
-            BrowserWindow::Chrome { page, .. } => {
-                let image_format = browser_specific::chrome_image_format(&filepath)?;
-
-                let element = browser_specific::wait_for_chrome_element_selector(
-                    page,
-                    selector,
-                    timeout_secs,
-                )
-                .await?;
-
-                element
-                    .save_screenshot(image_format, filepath)
-                    .await
-                    .map(|_| ())
-                    .map_err(|e| ToolproofStepError::Internal(e.into()))
-            }
+            BrowserWindow::Chrome { page, .. } => page
+                .screenshot(ScreenshotParams {
+                    cdp_params: CaptureScreenshotParams {
+                        format: Some(CaptureScreenshotFormat::Png),
+                        ..CaptureScreenshotParams::default()
+                    },
+                    full_page: Some(false),
+                    omit_background: Some(false),
+                })
+                .await
+                .map_err(|e| ToolproofStepError::Internal(e.into())),
             BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
                 ToolproofInternalError::Custom {
                     msg: "Screenshots not yet implemented for Pagebrowse".to_string(),
@@ -288,43 +902,184 @@ impl BrowserWindow {
         }
     }
 
-    async fn interact_text(
-        &self,
-        text: &str,
+    async fn accessibility_tree(&self) -> Result<serde_json::Value, ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, .. } => {
+                let tree = page
+                    .execute(GetFullAxTreeParams::default())
+                    .await
+                    .map_err(|e| ToolproofStepError::Internal(e.into()))?;
+
+                Ok(browser_specific::simplify_ax_tree(&tree.result.nodes))
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Accessibility trees not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
+    /// Screenshots the element bounds, optionally grown by `padding` CSS pixels on each side
+    /// and captured at `scale` times the device scale factor.
+    async fn screenshot_element(
+        &self,
+        selector: &str,
+        filepath: PathBuf,
+        timeout_secs: u64,
+        padding: f64,
+        scale: f64,
+    ) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, .. } => {
+                self.require_main_frame("Element screenshots")?;
+
+                let image_format = browser_specific::chrome_image_format(&filepath)?;
+
+                let element = browser_specific::wait_for_chrome_element_selector(
+                    page,
+                    selector,
+                    timeout_secs,
+                )
+                .await?;
+
+                if padding == 0.0 && scale == 1.0 {
+                    return element
+                        .save_screenshot(image_format, filepath)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| ToolproofStepError::Internal(e.into()));
+                }
+
+                let map_err = |inner: CdpError| ToolproofStepError::Internal(inner.into());
+
+                let bounds = element
+                    .scroll_into_view()
+                    .await
+                    .map_err(map_err)?
+                    .bounding_box()
+                    .await
+                    .map_err(map_err)?;
+                let viewport = page
+                    .layout_metrics()
+                    .await
+                    .map_err(map_err)?
+                    .css_layout_viewport;
+
+                // The element bounds are relative to the viewport, but the clip is relative to the page
+                let left = (viewport.page_x as f64 + bounds.x - padding).max(0.0);
+                let top = (viewport.page_y as f64 + bounds.y - padding).max(0.0);
+                let right = viewport.page_x as f64 + bounds.x + bounds.width + padding;
+                let bottom = viewport.page_y as f64 + bounds.y + bounds.height + padding;
+
+                page.save_screenshot(
+                    ScreenshotParams {
+                        cdp_params: CaptureScreenshotParams {
+                            format: Some(image_format),
+                            clip: Some(ClipViewport {
+                                x: left,
+                                y: top,
+                                width: right - left,
+                                height: bottom - top,
+                                scale,
+                            }),
+                            capture_beyond_viewport: Some(true),
+                            ..CaptureScreenshotParams::default()
+                        },
+                        full_page: Some(false),
+                        omit_background: Some(false),
+                    },
+                    filepath,
+                )
+                .await
+                .map(|_| ())
+                .map_err(map_err)
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Screenshots not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
+    /// Interacts with a clickable element by its text, optionally only looking
+    /// inside the elements matching the `within` selector.
+    /// If `nth` is given, the nth (1-indexed) matching element in document order is used
+    /// rather than erroring when more than one element matches.
+    async fn interact_text(
+        &self,
+        text: &str,
+        text_match: TextMatch,
+        within: Option<&str>,
+        nth: Option<usize>,
         interaction: InteractionType,
         timeout_secs: u64,
     ) -> Result<(), ToolproofStepError> {
         match self {
             BrowserWindow::Chrome { page, .. } => {
-                let text = text.to_lowercase();
+                self.require_main_frame("Text interactions")?;
+
+                let text = match text_match {
+                    TextMatch::Contains => text.to_lowercase(),
+                    TextMatch::Exact => text
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                        .to_lowercase(),
+                };
                 let selector_text = escape_xpath_string(&text);
-                let el_xpath = |el: &str| {
-                    format!("//{el}[contains(translate(., 'ABCDEFGHIJKLMNOPQRSTUVWXYZ', 'abcdefghijklmnopqrstuvwxyz'), {selector_text})]")
+                let lowercased =
+                    "translate(., 'ABCDEFGHIJKLMNOPQRSTUVWXYZ', 'abcdefghijklmnopqrstuvwxyz')";
+                let el_xpath = |el: &str| match text_match {
+                    TextMatch::Contains => {
+                        format!("//{el}[contains({lowercased}, {selector_text})]")
+                    }
+                    TextMatch::Exact => {
+                        format!("//{el}[normalize-space({lowercased}) = {selector_text}]")
+                    }
                 };
-                let xpath = [
+                let xpaths = [
                     el_xpath("a"),
                     el_xpath("button"),
                     el_xpath("input"),
                     el_xpath("option"),
                     el_xpath("*[@role='button']"),
                     el_xpath("*[@role='option']"),
-                ]
-                .join(" | ");
+                ];
+                let descriptor = format!("with text '{text}'");
 
                 loop {
-                    let elements = browser_specific::wait_for_chrome_xpath_selectors(
-                        page,
-                        &xpath,
-                        &format!("with text '{text}'"),
-                        timeout_secs,
-                    )
-                    .await?;
+                    let elements = match within {
+                        Some(container) => {
+                            browser_specific::wait_for_chrome_xpath_selectors_within(
+                                page,
+                                container,
+                                &xpaths,
+                                &descriptor,
+                                timeout_secs,
+                            )
+                            .await?
+                        }
+                        None => {
+                            browser_specific::wait_for_chrome_xpath_selectors(
+                                page,
+                                &xpaths.join(" | "),
+                                &descriptor,
+                                timeout_secs,
+                            )
+                            .await?
+                        }
+                    };
 
                     if elements.is_empty() {
+                        let within = within
+                            .map(|container| format!(" within {container}"))
+                            .unwrap_or_default();
                         return Err(ToolproofStepError::Assertion(
                             ToolproofTestFailure::Custom {
                                 msg: format!(
-                                    "Clickable element containing text '{text}' does not exist."
+                                    "Clickable element containing text '{text}'{within} does not exist."
                                 ),
                             },
                         ));
@@ -336,6 +1091,36 @@ impl BrowserWindow {
                     }))
                     .await;
 
+                    if let Some(nth) = nth {
+                        if nth > elements.len() {
+                            let found = elements.len();
+                            let options: String = elements
+                                .into_iter()
+                                .map(|(_, t)| format!("  - \"{t}\""))
+                                .collect::<Vec<_>>()
+                                .join("\n");
+                            return Err(ToolproofStepError::Assertion(
+                                ToolproofTestFailure::Custom {
+                                    msg: format!(
+                                        "Wanted match {nth} of the clickable elements containing text '{text}', but found {found}.\nOptions:\n{options}"
+                                    ),
+                                },
+                            ));
+                        }
+                        let chosen = elements.swap_remove(nth - 1);
+                        elements = vec![chosen];
+                    }
+
+                    if elements.len() > 1 && matches!(text_match, TextMatch::Exact) {
+                        return Err(ToolproofStepError::Assertion(
+                            ToolproofTestFailure::Custom {
+                                msg: format!(
+                                    "Found more than one clickable element with exactly the text '{text}'."
+                                ),
+                            },
+                        ));
+                    }
+
                     if elements.len() > 1 {
                         let exact_matches: usize = elements
                             .iter()
@@ -449,80 +1234,157 @@ impl BrowserWindow {
     ) -> Result<(), ToolproofStepError> {
         match self {
             BrowserWindow::Chrome { page, .. } => {
-                loop {
-                    let element = browser_specific::wait_for_chrome_element_selector(
-                        page,
-                        selector,
-                        timeout_secs,
-                    )
+                let center = self
+                    .selector_clickable_point(selector, timeout_secs)
                     .await?;
 
-                    if let Err(e) = element.scroll_into_view().await {
-                        match e {
-                            // If the element was detached from the DOM after the time we selected it,
-                            // we want to restart this section and select a new element.
-                            CdpError::ScrollingFailed(msg) if msg.contains("detached") => continue,
-                            _ => {
-                                return Err(ToolproofStepError::Assertion(
-                                    ToolproofTestFailure::Custom {
-                                        msg: format!(
-                                        "Element {selector} could not be scrolled into view: {e}"
-                                    ),
-                                    },
-                                ))
-                            }
-                        }
+                match interaction {
+                    InteractionType::Click => {
+                        page.click(center).await.map_err(|e| {
+                            ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+                                msg: format!("Element {selector} could not be clicked: {e}"),
+                            })
+                        })?;
                     }
+                    InteractionType::Hover => {
+                        page.move_mouse(center).await.map_err(|e| {
+                            ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+                                msg: format!("Element {selector} could not be hovered: {e}"),
+                            })
+                        })?;
+                    }
+                }
 
-                    let center = match element.clickable_point().await {
-                        Ok(c) => c,
-                        Err(e) => {
-                            if let Ok(res) = element
-                                .call_js_fn("async function() { return this.isConnected; }", true)
-                                .await
-                            {
-                                // If we can't find the center due to the element now being detached from the DOM,
-                                // we want to restart this section and select a new element.
-                                if matches!(res.result.value, Some(serde_json::Value::Bool(false)))
-                                {
-                                    continue;
-                                }
-                            }
+                Ok(())
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Clicks not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
 
-                            return Err(ToolproofStepError::Assertion(
-                                ToolproofTestFailure::Custom {
-                                    msg: format!(
-                                        "Could not find a clickable point for {selector}: {e}"
-                                    ),
-                                },
-                            ));
-                        }
+    async fn drag_selector(
+        &self,
+        from: &str,
+        to: &str,
+        timeout_secs: u64,
+    ) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, .. } => {
+                let start = self.selector_clickable_point(from, timeout_secs).await?;
+
+                let drag_err = |e: String| {
+                    ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+                        msg: format!("Element {from} could not be dragged to {to}: {e}"),
+                    })
+                };
+
+                let mouse_event = |event_type: DispatchMouseEventType, point: Point| {
+                    DispatchMouseEventParams::builder()
+                        .r#type(event_type)
+                        .x(point.x)
+                        .y(point.y)
+                        .button(MouseButton::Left)
+                        .click_count(1)
+                        .build()
+                        .map_err(drag_err)
+                };
+
+                page.move_mouse(start)
+                    .await
+                    .map_err(|e| drag_err(e.to_string()))?;
+                page.execute(mouse_event(DispatchMouseEventType::MousePressed, start)?)
+                    .await
+                    .map_err(|e| drag_err(e.to_string()))?;
+
+                // The target is located after pressing, since starting a drag
+                // can cause the page to change its layout
+                let end = self.selector_clickable_point(to, timeout_secs).await?;
+
+                // Move in steps so that pages listening for intermediate
+                // mouse movements see a realistic drag
+                for i in 1..=DRAG_STEPS {
+                    let progress = i as f64 / DRAG_STEPS as f64;
+                    let point = Point {
+                        x: start.x + (end.x - start.x) * progress,
+                        y: start.y + (end.y - start.y) * progress,
                     };
+                    page.execute(mouse_event(DispatchMouseEventType::MouseMoved, point)?)
+                        .await
+                        .map_err(|e| drag_err(e.to_string()))?;
+                }
 
-                    match interaction {
-                        InteractionType::Click => {
-                            page.click(center).await.map_err(|e| {
-                                ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
-                                    msg: format!("Element {selector} could not be clicked: {e}"),
-                                })
-                            })?;
-                        }
-                        InteractionType::Hover => {
-                            page.move_mouse(center).await.map_err(|e| {
-                                ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
-                                    msg: format!("Element {selector} could not be hovered: {e}"),
-                                })
-                            })?;
-                        }
-                    }
-                    break;
+                page.execute(mouse_event(DispatchMouseEventType::MouseReleased, end)?)
+                    .await
+                    .map_err(|e| drag_err(e.to_string()))?;
+
+                Ok(())
+            }
+            BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: "Dragging not yet implemented for Pagebrowse".to_string(),
+                },
+            )),
+        }
+    }
+
+    async fn upload_file(
+        &self,
+        selector: &str,
+        filepath: PathBuf,
+        timeout_secs: u64,
+    ) -> Result<(), ToolproofStepError> {
+        match self {
+            BrowserWindow::Chrome { page, .. } => {
+                self.require_main_frame("File uploads")?;
+
+                let element = browser_specific::wait_for_chrome_element_selector(
+                    page,
+                    selector,
+                    timeout_secs,
+                )
+                .await?;
+
+                let is_file_input = element
+                    .call_js_fn(
+                        "function() { return this.tagName === 'INPUT' && this.type === 'file'; }",
+                        false,
+                    )
+                    .await
+                    .map_err(|e| ToolproofStepError::Internal(e.into()))?;
+
+                if !matches!(
+                    is_file_input.result.value,
+                    Some(serde_json::Value::Bool(true))
+                ) {
+                    return Err(ToolproofStepError::Assertion(
+                        ToolproofTestFailure::Custom {
+                            msg: format!("Element {selector} is not a file input"),
+                        },
+                    ));
                 }
 
+                let params = SetFileInputFilesParams::builder()
+                    .file(filepath.to_string_lossy())
+                    .backend_node_id(element.backend_node_id)
+                    .build()
+                    .map_err(|e| {
+                        ToolproofStepError::Internal(ToolproofInternalError::Custom { msg: e })
+                    })?;
+
+                page.execute(params).await.map_err(|e| {
+                    ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
+                        msg: format!("File could not be uploaded to {selector}: {e}"),
+                    })
+                })?;
+
                 Ok(())
             }
             BrowserWindow::Pagebrowse(_) => Err(ToolproofStepError::Internal(
                 ToolproofInternalError::Custom {
-                    msg: "Clicks not yet implemented for Pagebrowse".to_string(),
+                    msg: "File uploads not yet implemented for Pagebrowse".to_string(),
                 },
             )),
         }
@@ -535,6 +1397,8 @@ impl BrowserWindow {
     ) -> Result<(), ToolproofStepError> {
         match self {
             BrowserWindow::Chrome { page, .. } => {
+                self.require_main_frame("Scrolls")?;
+
                 loop {
                     let element = browser_specific::wait_for_chrome_element_selector(
                         page,
@@ -599,16 +1463,1179 @@ impl BrowserWindow {
 mod load_page {
     use super::*;
 
-    pub struct LoadPage;
+    enum LoadUntil {
+        Load,
+        NetworkIdle,
+        Nothing,
+    }
+
+    async fn load_page(
+        args: &SegmentArgs<'_>,
+        civ: &mut Civilization<'_>,
+        until: LoadUntil,
+    ) -> Result<(), ToolproofStepError> {
+        let url = format!("{}{}", civ.server_origin(), args.get_string("url")?);
+
+        let browser = civ
+            .universe
+            .browser
+            .get_or_init(|| async { BrowserTester::initialize(&civ.universe.ctx.params).await })
+            .await
+            .as_ref()
+            .map_err(|msg| {
+                ToolproofStepError::Internal(ToolproofInternalError::Custom { msg: msg.clone() })
+            })?;
+
+        // The previous page is closed first, so that its browser context can be reused
+        if let Some(window) = civ.window.take() {
+            window.close().await;
+        }
+
+        // The window is kept before loading, so that it is still cleaned up
+        // (and can be screenshotted) if loading fails
+        let window = civ.window.insert(browser.get_window().await?);
+        window.apply_emulation(&civ.emulation).await?;
+
+        match until {
+            LoadUntil::Load => window.navigate(url, true).await?,
+            LoadUntil::NetworkIdle => window.navigate_and_wait_for_network_idle(url).await?,
+            LoadUntil::Nothing => window.navigate(url, false).await?,
+        }
+
+        Ok(())
+    }
+
+    pub struct LoadPage;
+
+    inventory::submit! {
+        &LoadPage as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for LoadPage {
+        fn segments(&self) -> &'static str {
+            "In my browser, I load {url}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            load_page(args, civ, LoadUntil::Load).await
+        }
+    }
+
+    pub struct LoadPageNetworkIdle;
+
+    inventory::submit! {
+        &LoadPageNetworkIdle as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for LoadPageNetworkIdle {
+        fn segments(&self) -> &'static str {
+            "In my browser, I load {url} and wait for network idle"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            load_page(args, civ, LoadUntil::NetworkIdle).await
+        }
+    }
+
+    pub struct LoadPageWithoutWaiting;
+
+    inventory::submit! {
+        &LoadPageWithoutWaiting as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for LoadPageWithoutWaiting {
+        fn segments(&self) -> &'static str {
+            "In my browser, I load {url} without waiting"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            load_page(args, civ, LoadUntil::Nothing).await
+        }
+    }
+
+    pub struct ReloadPage;
+
+    inventory::submit! {
+        &ReloadPage as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for ReloadPage {
+        fn segments(&self) -> &'static str {
+            "In my browser, I reload the page"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let Some(window) = civ.window.as_mut() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window.reload().await
+        }
+    }
+
+    pub struct GoBack;
+
+    inventory::submit! {
+        &GoBack as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for GoBack {
+        fn segments(&self) -> &'static str {
+            "In my browser, I go back"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let Some(window) = civ.window.as_mut() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window.navigate_history(-1).await
+        }
+    }
+
+    pub struct GoForward;
+
+    inventory::submit! {
+        &GoForward as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for GoForward {
+        fn segments(&self) -> &'static str {
+            "In my browser, I go forward"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let Some(window) = civ.window.as_mut() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window.navigate_history(1).await
+        }
+    }
+}
+
+mod emulation {
+    use super::*;
+
+    pub struct SetUserAgent;
+
+    inventory::submit! {
+        &SetUserAgent as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for SetUserAgent {
+        fn segments(&self) -> &'static str {
+            "In my browser, I set the user agent to {ua}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            // The loaded page has already sent its requests and read
+            // navigator.userAgent, so changing it now would not be seen
+            if civ.window.is_some() {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepError {
+                        reason: "The user agent must be set before a page is loaded in the browser"
+                            .to_string(),
+                    },
+                ));
+            }
+
+            civ.emulation.user_agent = Some(args.get_string("ua")?);
+
+            Ok(())
+        }
+    }
+
+    pub struct SetLocation;
+
+    inventory::submit! {
+        &SetLocation as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for SetLocation {
+        fn segments(&self) -> &'static str {
+            "In my browser, I set my location to {latitude} {longitude}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let latitude = args.get_f64("latitude")?;
+            let longitude = args.get_f64("longitude")?;
+
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepError {
+                        reason: format!(
+                            "{latitude} {longitude} is not a location, latitude must be between -90 and 90 and longitude between -180 and 180"
+                        ),
+                    },
+                ));
+            }
+
+            civ.emulation.geolocation = Some((latitude, longitude));
+
+            if let Some(window) = civ.window.as_ref() {
+                window.apply_emulation(&civ.emulation).await?;
+            }
+
+            Ok(())
+        }
+    }
+
+    async fn set_network(
+        civ: &mut Civilization<'_>,
+        conditions: Option<NetworkConditions>,
+    ) -> Result<(), ToolproofStepError> {
+        civ.emulation.network = conditions;
+
+        if let Some(window) = civ.window.as_ref() {
+            window
+                .emulate_network(&conditions.unwrap_or(NetworkConditions::ONLINE))
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub struct GoOffline;
+
+    inventory::submit! {
+        &GoOffline as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for GoOffline {
+        fn segments(&self) -> &'static str {
+            "In my browser, I go offline"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            set_network(civ, Some(NetworkConditions::OFFLINE)).await
+        }
+    }
+
+    pub struct GoOnline;
+
+    inventory::submit! {
+        &GoOnline as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for GoOnline {
+        fn segments(&self) -> &'static str {
+            "In my browser, I go online"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            set_network(civ, None).await
+        }
+    }
+
+    pub struct ThrottleNetwork;
+
+    inventory::submit! {
+        &ThrottleNetwork as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for ThrottleNetwork {
+        fn segments(&self) -> &'static str {
+            "In my browser, I throttle the network to {preset}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let preset = args.get_string("preset")?;
+
+            let Some(conditions) = NetworkConditions::preset(&preset) else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepError {
+                        reason: format!(
+                            "{preset:?} is not a network preset, expected one of: {}",
+                            NetworkConditions::PRESETS.join(", ")
+                        ),
+                    },
+                ));
+            };
+
+            set_network(civ, Some(conditions)).await
+        }
+    }
+}
+
+mod frames {
+    use super::*;
+
+    pub struct SwitchToFrame;
+
+    inventory::submit! {
+        &SwitchToFrame as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for SwitchToFrame {
+        fn segments(&self) -> &'static str {
+            "In my browser, I switch to the frame {selector}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let selector = args.get_string("selector")?;
+            let timeout_secs = auto_selector_timeout(civ);
+
+            let Some(window) = civ.window.as_mut() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window.switch_to_frame(&selector, timeout_secs).await
+        }
+    }
+
+    pub struct SwitchToMainFrame;
+
+    inventory::submit! {
+        &SwitchToMainFrame as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for SwitchToMainFrame {
+        fn segments(&self) -> &'static str {
+            "In my browser, I switch to the main frame"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let Some(window) = civ.window.as_mut() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window.switch_to_main_frame();
+
+            Ok(())
+        }
+    }
+}
+
+mod eval_js {
+
+    use crate::errors::{ToolproofInternalError, ToolproofTestFailure};
+
+    use super::*;
+
+    pub(super) async fn eval_and_return_js(
+        js: String,
+        civ: &mut Civilization<'_>,
+    ) -> Result<serde_json::Value, ToolproofStepError> {
+        let Some(window) = civ.window.as_ref() else {
+            return Err(ToolproofStepError::External(
+                ToolproofInputError::StepRequirementsNotMet {
+                    reason: "no page has been loaded into the browser for this test".into(),
+                },
+            ));
+        };
+
+        let value = window.evaluate_script(js).await?;
+
+        let Some(serde_json::Value::Object(map)) = &value else {
+            return Err(ToolproofStepError::External(
+                ToolproofInputError::StepError {
+                    reason: "JavaScript failed to parse and run".to_string(),
+                },
+            ));
+        };
+
+        let Some(serde_json::Value::Array(errors)) = map.get("toolproof_errs") else {
+            return Err(ToolproofStepError::Internal(
+                ToolproofInternalError::Custom {
+                    msg: format!("JavaScript returned an unexpected value: {value:?}"),
+                },
+            ));
+        };
+
+        if !errors.is_empty() {
+            return Err(ToolproofStepError::Assertion(
+                ToolproofTestFailure::BrowserJavascriptErr {
+                    msg: errors
+                        .iter()
+                        .map(|v| v.as_str().unwrap())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    logs: map.get("logs").unwrap().as_str().unwrap().to_string(),
+                },
+            ));
+        }
+
+        Ok(map
+            .get("inner_response")
+            .cloned()
+            .unwrap_or(serde_json::Value::Null))
+    }
+
+    pub struct EvalJs;
+
+    inventory::submit! {
+        &EvalJs as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for EvalJs {
+        fn segments(&self) -> &'static str {
+            "In my browser, I evaluate {js}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let js = args.get_string("js")?;
+
+            _ = eval_and_return_js(js, civ).await?;
+
+            Ok(())
+        }
+    }
+
+    /// Whether JavaScript would treat a returned value as true
+    fn is_truthy(value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::Null => false,
+            serde_json::Value::Bool(b) => *b,
+            serde_json::Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+            serde_json::Value::String(s) => !s.is_empty(),
+            serde_json::Value::Array(_) | serde_json::Value::Object(_) => true,
+        }
+    }
+
+    pub struct WaitUntil;
+
+    inventory::submit! {
+        &WaitUntil as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for WaitUntil {
+        fn segments(&self) -> &'static str {
+            "In my browser, I wait until {js}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let js = args.get_string("js")?;
+            let timeout_secs = auto_selector_timeout(civ);
+
+            let start = std::time::Instant::now();
+            loop {
+                let value = eval_and_return_js(js.clone(), civ).await?;
+                if is_truthy(&value) {
+                    return Ok(());
+                }
+
+                if start.elapsed().as_secs() >= timeout_secs {
+                    return Err(ToolproofStepError::Assertion(
+                        ToolproofTestFailure::Custom {
+                            msg: format!(
+                                "JavaScript did not return a truthy value within {timeout_secs}s, last returned {value}"
+                            ),
+                        },
+                    ));
+                }
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    pub struct GetJs;
+
+    inventory::submit! {
+        &GetJs as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetJs {
+        fn segments(&self) -> &'static str {
+            "In my browser, the result of {js}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let js = args.get_string("js")?;
+
+            eval_and_return_js(js, civ).await
+        }
+    }
+
+    pub struct GetConsole;
+
+    inventory::submit! {
+        &GetConsole as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetConsole {
+        fn segments(&self) -> &'static str {
+            "In my browser, the console"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            eval_and_return_js("return toolproof_log_events[`ALL`];".to_string(), civ).await
+        }
+    }
+
+    pub struct GetFailedRequests;
+
+    inventory::submit! {
+        &GetFailedRequests as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetFailedRequests {
+        fn segments(&self) -> &'static str {
+            "In my browser, the failed requests"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let Some(window) = civ.window.as_ref() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window.failed_requests()
+        }
+    }
+
+    pub struct GetClipboard;
+
+    inventory::submit! {
+        &GetClipboard as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetClipboard {
+        fn segments(&self) -> &'static str {
+            "In my browser, the clipboard text"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let js = r#"
+                try {
+                    return await navigator.clipboard.readText();
+                } catch (e) {
+                    throw new Error(`:toolproof_err: Could not read the clipboard, the browser may have denied permission (${e})`);
+                }
+            "#;
+
+            eval_and_return_js(js.to_string(), civ).await
+        }
+    }
+
+    pub struct GetSelectorTexts;
+
+    inventory::submit! {
+        &GetSelectorTexts as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetSelectorTexts {
+        fn segments(&self) -> &'static str {
+            "In my browser, the texts of the selector {selector}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let selector = args.get_string("selector")?;
+
+            let js = format!(
+                "return Array.from(document.querySelectorAll({})).map((el) => el.innerText);",
+                serde_json::Value::String(selector)
+            );
+
+            eval_and_return_js(js, civ).await
+        }
+    }
+
+    pub struct GetSelectorValue;
+
+    inventory::submit! {
+        &GetSelectorValue as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetSelectorValue {
+        fn segments(&self) -> &'static str {
+            "In my browser, the value of the selector {selector}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let selector = args.get_string("selector")?;
+
+            let js = format!(
+                r#"
+                const el = await toolproof.querySelector({});
+                return "value" in el ? el.value : null;
+                "#,
+                serde_json::Value::String(selector)
+            );
+
+            eval_and_return_js(js, civ).await
+        }
+    }
+
+    pub struct GetSelectorVisible;
+
+    inventory::submit! {
+        &GetSelectorVisible as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetSelectorVisible {
+        fn segments(&self) -> &'static str {
+            "In my browser, the selector {selector} is visible"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let selector = args.get_string("selector")?;
+
+            // An element can be in the DOM but not rendered, so we check
+            // its layout and computed style rather than just its presence
+            let js = format!(
+                r#"
+                const el = document.querySelector({});
+                if (!el) return false;
+                const style = window.getComputedStyle(el);
+                const rect = el.getBoundingClientRect();
+                return style.display !== "none"
+                    && style.visibility !== "hidden"
+                    && (el.offsetParent !== null || style.position === "fixed")
+                    && rect.width > 0
+                    && rect.height > 0;
+                "#,
+                serde_json::Value::String(selector)
+            );
+
+            eval_and_return_js(js, civ).await
+        }
+    }
+
+    pub struct GetSelectorBoundingBox;
+
+    inventory::submit! {
+        &GetSelectorBoundingBox as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetSelectorBoundingBox {
+        fn segments(&self) -> &'static str {
+            "In my browser, the bounding box of the selector {selector}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let selector = args.get_string("selector")?;
+
+            let js = format!(
+                r#"
+                const el = await toolproof.querySelector({});
+                const {{ x, y, width, height }} = el.getBoundingClientRect();
+                return {{ x, y, width, height }};
+                "#,
+                serde_json::Value::String(selector)
+            );
+
+            eval_and_return_js(js, civ).await
+        }
+    }
+}
+
+mod accessibility {
+
+    use super::eval_js::eval_and_return_js;
+    use super::*;
+
+    pub struct GetAccessibilityTree;
+
+    inventory::submit! {
+        &GetAccessibilityTree as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetAccessibilityTree {
+        fn segments(&self) -> &'static str {
+            "In my browser, the accessibility tree"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let Some(window) = civ.window.as_ref() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window.accessibility_tree().await
+        }
+    }
+
+    pub struct GetContrastIssues;
+
+    inventory::submit! {
+        &GetContrastIssues as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetContrastIssues {
+        fn segments(&self) -> &'static str {
+            "In my browser, the contrast issues"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            eval_and_return_js(CONTRAST_SCRIPT.to_string(), civ).await
+        }
+    }
+}
+
+mod performance {
+    use super::eval_js::eval_and_return_js;
+    use super::*;
+
+    pub struct GetPerformanceTiming;
+
+    inventory::submit! {
+        &GetPerformanceTiming as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetPerformanceTiming {
+        fn segments(&self) -> &'static str {
+            "In my browser, the performance timing"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let js = r#"
+                const [navigation] = performance.getEntriesByType("navigation");
+                if (navigation) return navigation.toJSON();
+
+                // Older browsers only have absolute timestamps,
+                // so make them relative to the start of the navigation like the entry above
+                const timing = performance.timing.toJSON();
+                const start = timing.navigationStart;
+                return Object.fromEntries(
+                    Object.entries(timing).map(([key, time]) => [key, time ? time - start : 0])
+                );
+            "#;
+
+            eval_and_return_js(js.to_string(), civ).await
+        }
+    }
+
+    pub struct GetTimeToFirstByte;
+
+    inventory::submit! {
+        &GetTimeToFirstByte as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetTimeToFirstByte {
+        fn segments(&self) -> &'static str {
+            "In my browser, the time to first byte"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let js = r#"
+                const [navigation] = performance.getEntriesByType("navigation");
+                if (navigation) return navigation.responseStart;
+                return performance.timing.responseStart - performance.timing.navigationStart;
+            "#;
+
+            eval_and_return_js(js.to_string(), civ).await
+        }
+    }
+}
+
+pub mod custom_steps {
+    use super::eval_js::eval_and_return_js;
+    use super::*;
+
+    use crate::definitions::ToolproofAssertion;
+    use crate::parser::parse_segments;
+
+    /// Wraps a custom step's JavaScript so that it can access
+    /// its arguments (and the value under test, for assertions)
+    fn js_with_inputs(
+        js: &str,
+        variables: &[String],
+        args: &SegmentArgs<'_>,
+        value: Option<&serde_json::Value>,
+    ) -> Result<String, ToolproofStepError> {
+        let mut inputs = serde_json::Map::new();
+        for variable in variables {
+            inputs.insert(variable.clone(), args.get_value(variable)?);
+        }
+
+        let mut prelude = format!("const args = {};\n", serde_json::Value::Object(inputs));
+        if let Some(value) = value {
+            prelude.push_str(&format!("const value = {value};\n"));
+        }
+
+        Ok(format!("{prelude}{js}"))
+    }
+
+    pub struct CustomRetriever {
+        segments: &'static str,
+        variables: Vec<String>,
+        js: String,
+    }
+
+    impl CustomRetriever {
+        pub fn new(segments: String, js: String) -> Result<Self, ToolproofInputError> {
+            let variables = parse_segments(&segments)?.get_variable_names();
+
+            Ok(Self {
+                segments: Box::leak(segments.into_boxed_str()),
+                variables,
+                js,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for CustomRetriever {
+        fn segments(&self) -> &'static str {
+            self.segments
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let js = js_with_inputs(&self.js, &self.variables, args, None)?;
+
+            eval_and_return_js(js, civ).await
+        }
+    }
+
+    pub struct CustomAssertion {
+        segments: &'static str,
+        variables: Vec<String>,
+        js: String,
+    }
+
+    impl CustomAssertion {
+        pub fn new(segments: String, js: String) -> Result<Self, ToolproofInputError> {
+            let variables = parse_segments(&segments)?.get_variable_names();
+
+            Ok(Self {
+                segments: Box::leak(segments.into_boxed_str()),
+                variables,
+                js,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl ToolproofAssertion for CustomAssertion {
+        fn segments(&self) -> &'static str {
+            self.segments
+        }
+
+        async fn run(
+            &self,
+            base_value: serde_json::Value,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let js = js_with_inputs(&self.js, &self.variables, args, Some(&base_value))?;
+
+            _ = eval_and_return_js(js, civ).await?;
+
+            Ok(())
+        }
+    }
+}
+
+pub mod screenshots {
+
+    use super::*;
+
+    pub struct ScreenshotViewport;
+
+    inventory::submit! {
+        &ScreenshotViewport as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for ScreenshotViewport {
+        fn segments(&self) -> &'static str {
+            "In my browser, I screenshot the viewport to {filepath}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let filepath = args.get_string("filepath")?;
+            let resolved_path = civ.tmp_file_path(&filepath);
+            civ.ensure_path(&resolved_path);
+
+            let Some(window) = civ.window.as_ref() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window.screenshot_page(resolved_path).await
+        }
+    }
+
+    pub struct CompareViewport;
+
+    inventory::submit! {
+        &CompareViewport as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for CompareViewport {
+        fn segments(&self) -> &'static str {
+            "In my browser, I compare the viewport to the image {filename}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let filename = args.get_string("filename")?;
+            if !filename.to_lowercase().ends_with(".png") {
+                return Err(ToolproofInputError::StepRequirementsNotMet {
+                    reason: "Baseline images must be png files".to_string(),
+                }
+                .into());
+            }
+
+            let Some(window) = civ.window.as_ref() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            let screenshot = window.screenshot_page_png().await?;
+
+            // Baselines live next to the test, rather than in the temporary test directory
+            let baseline_path = civ.source_file_path(&filename);
+            let actual_path = baseline_path.with_extension("actual.png");
+            let diff_path = baseline_path.with_extension("diff.png");
+            civ.ensure_path(&baseline_path);
+
+            let internal_err =
+                |msg: String| ToolproofStepError::Internal(ToolproofInternalError::Custom { msg });
+
+            if civ.universe.ctx.params.update {
+                std::fs::write(&baseline_path, &screenshot).map_err(|e| {
+                    internal_err(format!("Unable to write baseline image {filename}: {e}"))
+                })?;
+                _ = std::fs::remove_file(&actual_path);
+                _ = std::fs::remove_file(&diff_path);
+                return Ok(());
+            }
+
+            let failure = if baseline_path.exists() {
+                let baseline = image::open(&baseline_path)
+                    .map_err(|e| internal_err(format!("Unable to read {filename}: {e}")))?
+                    .to_rgba8();
+                let actual = image::load_from_memory(&screenshot)
+                    .map_err(|e| internal_err(format!("Unable to read screenshot: {e}")))?
+                    .to_rgba8();
+
+                match image_diff::diff_images(&baseline, &actual) {
+                    Some(diff)
+                        if diff.difference <= civ.universe.ctx.params.image_diff_tolerance =>
+                    {
+                        _ = std::fs::remove_file(&actual_path);
+                        _ = std::fs::remove_file(&diff_path);
+                        return Ok(());
+                    }
+                    Some(diff) => {
+                        diff.diff_image.save(&diff_path).map_err(|e| {
+                            internal_err(format!("Unable to write diff image: {e}"))
+                        })?;
+                        format!(
+                            "Screenshot differs from {filename} by {:.2}% of pixels. See {} for the differences",
+                            diff.difference * 100.0,
+                            diff_path.to_string_lossy()
+                        )
+                    }
+                    None => format!(
+                        "Screenshot is {}x{} but {filename} is {}x{}",
+                        actual.width(),
+                        actual.height(),
+                        baseline.width(),
+                        baseline.height()
+                    ),
+                }
+            } else {
+                format!(
+                    "No baseline image exists at {}",
+                    baseline_path.to_string_lossy()
+                )
+            };
+
+            std::fs::write(&actual_path, &screenshot)
+                .map_err(|e| internal_err(format!("Unable to write screenshot: {e}")))?;
+            civ.changed_images.push((baseline_path, actual_path));
+
+            Err(ToolproofTestFailure::Custom {
+                msg: format!(
+                    "{failure}\nRun in interactive mode (-i) or with --update (-u) to accept the new screenshot"
+                ),
+            }
+            .into())
+        }
+    }
+
+    pub struct ScreenshotElement;
 
     inventory::submit! {
-        &LoadPage as &dyn ToolproofInstruction
+        &ScreenshotElement as &dyn ToolproofInstruction
     }
 
     #[async_trait]
-    impl ToolproofInstruction for LoadPage {
+    impl ToolproofInstruction for ScreenshotElement {
         fn segments(&self) -> &'static str {
-            "In my browser, I load {url}"
+            "In my browser, I screenshot the element {selector} to {filepath}"
         }
 
         async fn run(
@@ -616,39 +2643,50 @@ mod load_page {
             args: &SegmentArgs<'_>,
             civ: &mut Civilization,
         ) -> Result<(), ToolproofStepError> {
-            let url = format!(
-                "http://localhost:{}{}",
-                civ.ensure_port(),
-                args.get_string("url")?
-            );
-
-            let browser = civ
-                .universe
-                .browser
-                .get_or_init(|| async { BrowserTester::initialize(&civ.universe.ctx.params).await })
-                .await;
-
-            let window = browser.get_window().await;
-
-            window.navigate(url.to_string(), true).await?;
+            let selector = args.get_string("selector")?;
+            let filepath = args.get_string("filepath")?;
+            let resolved_path = civ.tmp_file_path(&filepath);
+            civ.ensure_path(&resolved_path);
 
-            civ.window = Some(window);
+            let Some(window) = civ.window.as_ref() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
 
-            Ok(())
+            window
+                .screenshot_element(
+                    &selector,
+                    resolved_path,
+                    auto_selector_timeout(civ),
+                    0.0,
+                    1.0,
+                )
+                .await
         }
     }
-}
 
-mod eval_js {
-
-    use crate::errors::{ToolproofInternalError, ToolproofTestFailure};
+    async fn screenshot_padded_element(
+        args: &SegmentArgs<'_>,
+        civ: &mut Civilization<'_>,
+        scale: f64,
+    ) -> Result<(), ToolproofStepError> {
+        let selector = args.get_string("selector")?;
+        let filepath = args.get_string("filepath")?;
+        let padding = args.get_f64("padding")?;
+        if padding < 0.0 || scale <= 0.0 {
+            return Err(ToolproofInputError::StepError {
+                reason: "padding can't be negative, and scale must be greater than zero"
+                    .to_string(),
+            }
+            .into());
+        }
 
-    use super::*;
+        let resolved_path = civ.tmp_file_path(&filepath);
+        civ.ensure_path(&resolved_path);
 
-    async fn eval_and_return_js(
-        js: String,
-        civ: &mut Civilization<'_>,
-    ) -> Result<serde_json::Value, ToolproofStepError> {
         let Some(window) = civ.window.as_ref() else {
             return Err(ToolproofStepError::External(
                 ToolproofInputError::StepRequirementsNotMet {
@@ -657,53 +2695,27 @@ mod eval_js {
             ));
         };
 
-        let value = window.evaluate_script(js).await?;
-
-        let Some(serde_json::Value::Object(map)) = &value else {
-            return Err(ToolproofStepError::External(
-                ToolproofInputError::StepError {
-                    reason: "JavaScript failed to parse and run".to_string(),
-                },
-            ));
-        };
-
-        let Some(serde_json::Value::Array(errors)) = map.get("toolproof_errs") else {
-            return Err(ToolproofStepError::Internal(
-                ToolproofInternalError::Custom {
-                    msg: format!("JavaScript returned an unexpected value: {value:?}"),
-                },
-            ));
-        };
-
-        if !errors.is_empty() {
-            return Err(ToolproofStepError::Assertion(
-                ToolproofTestFailure::BrowserJavascriptErr {
-                    msg: errors
-                        .iter()
-                        .map(|v| v.as_str().unwrap())
-                        .collect::<Vec<_>>()
-                        .join("\n"),
-                    logs: map.get("logs").unwrap().as_str().unwrap().to_string(),
-                },
-            ));
-        }
-
-        Ok(map
-            .get("inner_response")
-            .cloned()
-            .unwrap_or(serde_json::Value::Null))
+        window
+            .screenshot_element(
+                &selector,
+                resolved_path,
+                auto_selector_timeout(civ),
+                padding,
+                scale,
+            )
+            .await
     }
 
-    pub struct EvalJs;
+    pub struct ScreenshotPaddedElement;
 
     inventory::submit! {
-        &EvalJs as &dyn ToolproofInstruction
+        &ScreenshotPaddedElement as &dyn ToolproofInstruction
     }
 
     #[async_trait]
-    impl ToolproofInstruction for EvalJs {
+    impl ToolproofInstruction for ScreenshotPaddedElement {
         fn segments(&self) -> &'static str {
-            "In my browser, I evaluate {js}"
+            "In my browser, I screenshot the element {selector} to {filepath} with padding {padding}"
         }
 
         async fn run(
@@ -711,73 +2723,88 @@ mod eval_js {
             args: &SegmentArgs<'_>,
             civ: &mut Civilization,
         ) -> Result<(), ToolproofStepError> {
-            let js = args.get_string("js")?;
-
-            _ = eval_and_return_js(js, civ).await?;
-
-            Ok(())
+            screenshot_padded_element(args, civ, 1.0).await
         }
     }
 
-    pub struct GetJs;
+    pub struct ScreenshotScaledElement;
 
     inventory::submit! {
-        &GetJs as &dyn ToolproofRetriever
+        &ScreenshotScaledElement as &dyn ToolproofInstruction
     }
 
     #[async_trait]
-    impl ToolproofRetriever for GetJs {
+    impl ToolproofInstruction for ScreenshotScaledElement {
         fn segments(&self) -> &'static str {
-            "In my browser, the result of {js}"
+            "In my browser, I screenshot the element {selector} to {filepath} with padding {padding} at scale {scale}"
         }
 
         async fn run(
             &self,
             args: &SegmentArgs<'_>,
             civ: &mut Civilization,
-        ) -> Result<serde_json::Value, ToolproofStepError> {
-            let js = args.get_string("js")?;
+        ) -> Result<(), ToolproofStepError> {
+            let scale = args.get_f64("scale")?;
 
-            eval_and_return_js(js, civ).await
+            screenshot_padded_element(args, civ, scale).await
         }
     }
+}
 
-    pub struct GetConsole;
+mod interactions {
+    use super::eval_js::eval_and_return_js;
+    use super::*;
+
+    pub struct ClickText;
 
     inventory::submit! {
-        &GetConsole as &dyn ToolproofRetriever
+        &ClickText as &dyn ToolproofInstruction
     }
 
     #[async_trait]
-    impl ToolproofRetriever for GetConsole {
+    impl ToolproofInstruction for ClickText {
         fn segments(&self) -> &'static str {
-            "In my browser, the console"
+            "In my browser, I click {text}"
         }
 
         async fn run(
             &self,
-            _args: &SegmentArgs<'_>,
+            args: &SegmentArgs<'_>,
             civ: &mut Civilization,
-        ) -> Result<serde_json::Value, ToolproofStepError> {
-            eval_and_return_js("return toolproof_log_events[`ALL`];".to_string(), civ).await
-        }
-    }
-}
+        ) -> Result<(), ToolproofStepError> {
+            let text = args.get_string("text")?;
 
-pub mod screenshots {
+            let Some(window) = civ.window.as_ref() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
 
-    use super::*;
+            window
+                .interact_text(
+                    &text,
+                    TextMatch::Contains,
+                    None,
+                    None,
+                    InteractionType::Click,
+                    auto_selector_timeout(civ),
+                )
+                .await
+        }
+    }
 
-    pub struct ScreenshotViewport;
+    pub struct ClickTextMatch;
 
     inventory::submit! {
-        &ScreenshotViewport as &dyn ToolproofInstruction
+        &ClickTextMatch as &dyn ToolproofInstruction
     }
 
     #[async_trait]
-    impl ToolproofInstruction for ScreenshotViewport {
+    impl ToolproofInstruction for ClickTextMatch {
         fn segments(&self) -> &'static str {
-            "In my browser, I screenshot the viewport to {filepath}"
+            "In my browser, I click {text} (match {n})"
         }
 
         async fn run(
@@ -785,9 +2812,14 @@ pub mod screenshots {
             args: &SegmentArgs<'_>,
             civ: &mut Civilization,
         ) -> Result<(), ToolproofStepError> {
-            let filepath = args.get_string("filepath")?;
-            let resolved_path = civ.tmp_file_path(&filepath);
-            civ.ensure_path(&resolved_path);
+            let text = args.get_string("text")?;
+            let n = args.get_f64("n")?;
+            if n < 1.0 || n.fract() != 0.0 {
+                return Err(ToolproofInputError::StepError {
+                    reason: format!("match should be a whole number starting from 1, not {n}"),
+                }
+                .into());
+            }
 
             let Some(window) = civ.window.as_ref() else {
                 return Err(ToolproofStepError::External(
@@ -797,20 +2829,29 @@ pub mod screenshots {
                 ));
             };
 
-            window.screenshot_page(resolved_path).await
+            window
+                .interact_text(
+                    &text,
+                    TextMatch::Contains,
+                    None,
+                    Some(n as usize),
+                    InteractionType::Click,
+                    auto_selector_timeout(civ),
+                )
+                .await
         }
     }
 
-    pub struct ScreenshotElement;
+    pub struct ClickExactText;
 
     inventory::submit! {
-        &ScreenshotElement as &dyn ToolproofInstruction
+        &ClickExactText as &dyn ToolproofInstruction
     }
 
     #[async_trait]
-    impl ToolproofInstruction for ScreenshotElement {
+    impl ToolproofInstruction for ClickExactText {
         fn segments(&self) -> &'static str {
-            "In my browser, I screenshot the element {selector} to {filepath}"
+            "In my browser, I click the exact text {text}"
         }
 
         async fn run(
@@ -818,10 +2859,7 @@ pub mod screenshots {
             args: &SegmentArgs<'_>,
             civ: &mut Civilization,
         ) -> Result<(), ToolproofStepError> {
-            let selector = args.get_string("selector")?;
-            let filepath = args.get_string("filepath")?;
-            let resolved_path = civ.tmp_file_path(&filepath);
-            civ.ensure_path(&resolved_path);
+            let text = args.get_string("text")?;
 
             let Some(window) = civ.window.as_ref() else {
                 return Err(ToolproofStepError::External(
@@ -832,25 +2870,28 @@ pub mod screenshots {
             };
 
             window
-                .screenshot_element(&selector, resolved_path, auto_selector_timeout(civ))
+                .interact_text(
+                    &text,
+                    TextMatch::Exact,
+                    None,
+                    None,
+                    InteractionType::Click,
+                    auto_selector_timeout(civ),
+                )
                 .await
         }
     }
-}
-
-mod interactions {
-    use super::*;
 
-    pub struct ClickText;
+    pub struct ClickTextWithin;
 
     inventory::submit! {
-        &ClickText as &dyn ToolproofInstruction
+        &ClickTextWithin as &dyn ToolproofInstruction
     }
 
     #[async_trait]
-    impl ToolproofInstruction for ClickText {
+    impl ToolproofInstruction for ClickTextWithin {
         fn segments(&self) -> &'static str {
-            "In my browser, I click {text}"
+            "In my browser, within the selector {container}, I click {text}"
         }
 
         async fn run(
@@ -858,6 +2899,7 @@ mod interactions {
             args: &SegmentArgs<'_>,
             civ: &mut Civilization,
         ) -> Result<(), ToolproofStepError> {
+            let container = args.get_string("container")?;
             let text = args.get_string("text")?;
 
             let Some(window) = civ.window.as_ref() else {
@@ -869,7 +2911,14 @@ mod interactions {
             };
 
             window
-                .interact_text(&text, InteractionType::Click, auto_selector_timeout(civ))
+                .interact_text(
+                    &text,
+                    TextMatch::Contains,
+                    Some(&container),
+                    None,
+                    InteractionType::Click,
+                    auto_selector_timeout(civ),
+                )
                 .await
         }
     }
@@ -902,7 +2951,14 @@ mod interactions {
             };
 
             window
-                .interact_text(&text, InteractionType::Hover, auto_selector_timeout(civ))
+                .interact_text(
+                    &text,
+                    TextMatch::Contains,
+                    None,
+                    None,
+                    InteractionType::Hover,
+                    auto_selector_timeout(civ),
+                )
                 .await
         }
     }
@@ -1014,6 +3070,129 @@ mod interactions {
         }
     }
 
+    pub struct DragSelector;
+
+    inventory::submit! {
+        &DragSelector as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for DragSelector {
+        fn segments(&self) -> &'static str {
+            "In my browser, I drag the selector {from} to the selector {to}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let from = args.get_string("from")?;
+            let to = args.get_string("to")?;
+
+            let Some(window) = civ.window.as_ref() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window
+                .drag_selector(&from, &to, auto_selector_timeout(civ))
+                .await
+        }
+    }
+
+    pub struct UploadFile;
+
+    inventory::submit! {
+        &UploadFile as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for UploadFile {
+        fn segments(&self) -> &'static str {
+            "In my browser, I upload the file {filename} to the selector {selector}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let filename = args.get_string("filename")?;
+            let selector = args.get_string("selector")?;
+            let resolved_path = civ.tmp_file_path(&filename);
+
+            if !resolved_path.exists() {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: format!("the file {filename} does not exist"),
+                    },
+                ));
+            }
+
+            let Some(window) = civ.window.as_ref() else {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepRequirementsNotMet {
+                        reason: "no page has been loaded into the browser for this test".into(),
+                    },
+                ));
+            };
+
+            window
+                .upload_file(&selector, resolved_path, auto_selector_timeout(civ))
+                .await
+        }
+    }
+
+    pub struct SelectOption;
+
+    inventory::submit! {
+        &SelectOption as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for SelectOption {
+        fn segments(&self) -> &'static str {
+            "In my browser, I select the option {value} in {selector}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let value = serde_json::Value::String(args.get_string("value")?);
+            let selector = serde_json::Value::String(args.get_string("selector")?);
+
+            // Options are matched on their value first, falling back to their visible label
+            let js = format!(
+                r#"
+                const select = await toolproof.querySelector({selector});
+                if (!(select instanceof HTMLSelectElement)) {{
+                    throw new Error(`:toolproof_err: Element ${{{selector}}} is not a select element`);
+                }}
+                const options = Array.from(select.options);
+                const option = options.find((o) => o.value === {value})
+                    ?? options.find((o) => o.label.trim() === {value});
+                if (!option) {{
+                    const available = options.map((o) => `  - ${{JSON.stringify(o.value)}} (${{o.label.trim()}})`).join("\n");
+                    throw new Error(`:toolproof_err: No option matching ${{JSON.stringify({value})}} in ${{{selector}}}.\nOptions:\n${{available}}`);
+                }}
+                select.value = option.value;
+                select.dispatchEvent(new Event("input", {{ bubbles: true }}));
+                select.dispatchEvent(new Event("change", {{ bubbles: true }}));
+                "#
+            );
+
+            _ = eval_and_return_js(js, civ).await?;
+
+            Ok(())
+        }
+    }
+
     pub struct PressKey;
 
     inventory::submit! {
@@ -43,6 +43,103 @@ mod new_file {
     }
 }
 
+mod new_binary_file {
+    use base64::Engine;
+
+    use super::*;
+
+    pub struct NewBinaryFile;
+
+    inventory::submit! {
+        &NewBinaryFile as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for NewBinaryFile {
+        fn segments(&self) -> &'static str {
+            "I have a {filename} file with the base64 content {contents}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let filename = args.get_string("filename")?;
+            if filename.is_empty() {
+                return Err(ToolproofInputError::ArgumentRequiresValue {
+                    arg: "filename".to_string(),
+                }
+                .into());
+            }
+
+            let contents = args.get_string("contents")?;
+            // Long base64 strings are often wrapped across lines in YAML
+            let contents: String = contents.split_whitespace().collect();
+
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(contents)
+                .map_err(|e| ToolproofInputError::StepError {
+                    reason: format!("The contents of {filename} are not valid base64: {e}"),
+                })?;
+
+            civ.write_file_bytes(&filename, &bytes);
+
+            Ok(())
+        }
+    }
+}
+
+mod copy_source_file {
+
+    use super::*;
+
+    pub struct CopySourceFile;
+
+    inventory::submit! {
+        &CopySourceFile as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for CopySourceFile {
+        fn segments(&self) -> &'static str {
+            "I copy the source file {from} into {to}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let from = args.get_string("from")?;
+            if from.is_empty() {
+                return Err(ToolproofInputError::ArgumentRequiresValue {
+                    arg: "from".to_string(),
+                }
+                .into());
+            }
+
+            let to = args.get_string("to")?;
+            if to.is_empty() {
+                return Err(ToolproofInputError::ArgumentRequiresValue {
+                    arg: "to".to_string(),
+                }
+                .into());
+            }
+
+            let contents =
+                civ.read_source_file_bytes(&from)
+                    .map_err(|e| ToolproofInputError::StepError {
+                        reason: format!("Could not copy the source file {from}: {e}"),
+                    })?;
+
+            civ.write_file_bytes(&to, &contents);
+
+            Ok(())
+        }
+    }
+}
+
 mod read_files {
 
     use super::*;
@@ -78,4 +175,296 @@ mod read_files {
             Ok(serde_json::Value::String(contents))
         }
     }
+
+    pub struct SourceFile;
+
+    inventory::submit! {
+        &SourceFile as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for SourceFile {
+        fn segments(&self) -> &'static str {
+            "The source file {filename}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let filename = args.get_string("filename")?;
+
+            if filename.is_empty() {
+                return Err(ToolproofInputError::ArgumentRequiresValue {
+                    arg: "filename".to_string(),
+                }
+                .into());
+            }
+
+            let contents = civ.read_source_file(&filename)?;
+
+            Ok(serde_json::Value::String(contents))
+        }
+    }
+}
+
+mod read_lines {
+
+    use super::*;
+
+    /// Reads a 1-indexed line number, which may be given
+    /// as a number or as a string when written inline
+    fn get_line_number(args: &SegmentArgs<'_>, arg: &str) -> Result<usize, ToolproofStepError> {
+        let value = args.get_value(arg)?;
+        let line = match &value {
+            serde_json::Value::Number(n) => n.as_u64().map(|n| n as usize),
+            serde_json::Value::String(s) => s.trim().parse::<usize>().ok(),
+            _ => None,
+        };
+
+        match line {
+            Some(line) if line > 0 => Ok(line),
+            _ => Err(ToolproofInputError::StepError {
+                reason: format!("{arg} must be a line number of 1 or more, but was {value}"),
+            }
+            .into()),
+        }
+    }
+
+    fn get_lines(
+        args: &SegmentArgs<'_>,
+        civ: &mut Civilization,
+        start: usize,
+        end: usize,
+    ) -> Result<serde_json::Value, ToolproofStepError> {
+        let filename = args.get_string("filename")?;
+
+        if filename.is_empty() {
+            return Err(ToolproofInputError::ArgumentRequiresValue {
+                arg: "filename".to_string(),
+            }
+            .into());
+        }
+
+        if end < start {
+            return Err(ToolproofInputError::StepError {
+                reason: format!("line range {start} to {end} ends before it starts"),
+            }
+            .into());
+        }
+
+        let contents = civ.read_file(&filename)?;
+        let lines: Vec<_> = contents.lines().collect();
+
+        if end > lines.len() {
+            return Err(ToolproofInputError::StepError {
+                reason: format!(
+                    "line {end} is out of range, {filename} has {} lines",
+                    lines.len()
+                ),
+            }
+            .into());
+        }
+
+        Ok(serde_json::Value::String(lines[start - 1..end].join("\n")))
+    }
+
+    pub struct FileLine;
+
+    inventory::submit! {
+        &FileLine as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for FileLine {
+        fn segments(&self) -> &'static str {
+            "Line {n} of the file {filename}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let n = get_line_number(args, "n")?;
+
+            get_lines(args, civ, n, n)
+        }
+    }
+
+    pub struct FileLines;
+
+    inventory::submit! {
+        &FileLines as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for FileLines {
+        fn segments(&self) -> &'static str {
+            "Lines {start} to {end} of the file {filename}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let start = get_line_number(args, "start")?;
+            let end = get_line_number(args, "end")?;
+
+            get_lines(args, civ, start, end)
+        }
+    }
+}
+
+mod csv_file {
+    use crate::errors::ToolproofTestFailure;
+
+    use super::*;
+
+    /// Parses CSV into an array of objects, keyed by the header row
+    fn parse_csv(filename: &str, contents: &str) -> Result<serde_json::Value, ToolproofStepError> {
+        let invalid = |e: csv::Error| ToolproofTestFailure::Custom {
+            msg: format!("The file {filename} is not valid CSV: {e}"),
+        };
+
+        let mut reader = csv::Reader::from_reader(contents.as_bytes());
+        let headers = reader.headers().map_err(invalid)?.clone();
+
+        let mut rows = vec![];
+        for record in reader.records() {
+            let record = record.map_err(invalid)?;
+            let row = headers
+                .iter()
+                .zip(record.iter())
+                .map(|(header, field)| {
+                    (
+                        header.to_string(),
+                        serde_json::Value::String(field.to_string()),
+                    )
+                })
+                .collect();
+            rows.push(serde_json::Value::Object(row));
+        }
+
+        Ok(serde_json::Value::Array(rows))
+    }
+
+    pub struct CsvFile;
+
+    inventory::submit! {
+        &CsvFile as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for CsvFile {
+        fn segments(&self) -> &'static str {
+            "The file {filename} as csv"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let filename = args.get_string("filename")?;
+
+            if filename.is_empty() {
+                return Err(ToolproofInputError::ArgumentRequiresValue {
+                    arg: "filename".to_string(),
+                }
+                .into());
+            }
+
+            let contents = civ.read_file(&filename)?;
+
+            parse_csv(&filename, &contents)
+        }
+    }
+}
+
+mod file_hash {
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    pub struct FileSha256;
+
+    inventory::submit! {
+        &FileSha256 as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for FileSha256 {
+        fn segments(&self) -> &'static str {
+            "The sha256 of the file {filename}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let filename = args.get_string("filename")?;
+
+            if filename.is_empty() {
+                return Err(ToolproofInputError::ArgumentRequiresValue {
+                    arg: "filename".to_string(),
+                }
+                .into());
+            }
+
+            let contents = civ.read_file_bytes(&filename)?;
+            let digest = Sha256::digest(&contents);
+
+            Ok(serde_json::Value::String(format!("{digest:x}")))
+        }
+    }
+}
+
+mod file_tree {
+    use super::*;
+
+    pub struct FileCount;
+
+    inventory::submit! {
+        &FileCount as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for FileCount {
+        fn segments(&self) -> &'static str {
+            "The file count"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            Ok(serde_json::Value::from(civ.get_file_count()))
+        }
+    }
+
+    pub struct FileTree;
+
+    inventory::submit! {
+        &FileTree as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for FileTree {
+        fn segments(&self) -> &'static str {
+            "The file tree"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            Ok(serde_json::Value::String(civ.get_file_tree()))
+        }
+    }
 }
@@ -1,35 +1,119 @@
 use super::{SegmentArgs, ToolproofInstruction};
 use crate::civilization::Civilization;
-use crate::errors::ToolproofStepError;
+use crate::errors::{ToolproofInputError, ToolproofInternalError, ToolproofStepError};
 
 use async_trait::async_trait;
 
 mod host_dir {
     use std::time::Duration;
 
-    use actix_web::{App, HttpServer};
+    use actix_web::{http::header, web, App, HttpRequest, HttpResponse, HttpServer};
+    use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer};
     use schematic::color::owo::OwoColorize;
     use tokio::time::sleep;
 
     use super::*;
+    use crate::certificate::local_certificate;
+    use crate::logging::log_println;
 
-    async fn host(dir: &String, civ: &mut Civilization<'_>) -> Result<(), ToolproofStepError> {
+    /// Serves the certificate that Toolproof's browser and HTTP client trust for localhost
+    fn local_tls_config() -> Result<rustls::ServerConfig, ToolproofStepError> {
+        let map_err = |msg: String| {
+            ToolproofStepError::Internal(ToolproofInternalError::Custom {
+                msg: format!("Failed to create a certificate for localhost: {msg}"),
+            })
+        };
+
+        let certificate = local_certificate().map_err(map_err)?;
+
+        rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(
+                certificate.chain.clone(),
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certificate.key_der.clone())),
+            )
+            .map_err(|e| map_err(e.to_string()))
+    }
+
+    struct ProxyUpstream(String);
+
+    /// Forwards a request to the upstream of the proxy it was matched by,
+    /// keeping the full path so that `/api/users` is sent to `{upstream}/api/users`
+    async fn proxy_request(
+        req: HttpRequest,
+        payload: web::Payload,
+        upstream: web::Data<ProxyUpstream>,
+    ) -> Result<HttpResponse, actix_web::Error> {
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+        let url = format!("{}{path}", upstream.0.trim_end_matches('/'));
+
+        let mut forwarded = awc::Client::default()
+            .request_from(url, req.head())
+            .no_decompress();
+        forwarded.headers_mut().remove(header::HOST);
+
+        let res = forwarded
+            .send_stream(payload)
+            .await
+            .map_err(actix_web::error::ErrorBadGateway)?;
+
+        let mut client_res = HttpResponse::build(res.status());
+        // Appended rather than inserted, to keep headers that are sent more than once,
+        // such as Set-Cookie
+        for (name, value) in res.headers().iter() {
+            if name != header::CONNECTION {
+                client_res.append_header((name.clone(), value.clone()));
+            }
+        }
+
+        Ok(client_res.streaming(res))
+    }
+
+    async fn host(
+        dir: &String,
+        https: bool,
+        civ: &mut Civilization<'_>,
+    ) -> Result<(), ToolproofStepError> {
         civ.stop_servers().await;
 
+        let tls_config = if https {
+            Some(local_tls_config()?)
+        } else {
+            None
+        };
+
         let mut attempts = 0;
         let mut running = false;
         while !running && attempts < 5 {
             let port = civ.ensure_port();
             let dir = civ.tmp_file_path(&dir);
-            match HttpServer::new(move || {
-                App::new().service(
+            let proxies = civ.proxies.clone();
+            let server = HttpServer::new(move || {
+                let mut app = App::new();
+                for (prefix, upstream) in &proxies {
+                    app = app.service(
+                        web::scope(prefix)
+                            .app_data(web::Data::new(ProxyUpstream(upstream.clone())))
+                            .default_service(web::to(proxy_request)),
+                    );
+                }
+                app.service(
                     actix_files::Files::new("/", &dir)
                         .index_file("index.html")
                         .use_hidden_files(),
                 )
-            })
-            .bind(("127.0.0.1", port))
-            {
+            });
+            let bound = match &tls_config {
+                Some(tls_config) => {
+                    server.bind_rustls_0_23(("127.0.0.1", port), tls_config.clone())
+                }
+                None => server.bind(("127.0.0.1", port)),
+            };
+            match bound {
                 Ok(bound) => {
                     let server = bound.run();
                     let handle = server.handle();
@@ -45,6 +129,7 @@ mod host_dir {
         }
 
         assert!(running);
+        civ.serving_https = https;
         // Wait a beat to make sure the server is ready to roll
         sleep(Duration::from_millis(100)).await;
 
@@ -70,7 +155,70 @@ mod host_dir {
         ) -> Result<(), ToolproofStepError> {
             let dir = args.get_string("dir")?;
 
-            host(&dir, civ).await
+            civ.proxies.clear();
+            host(&dir, false, civ).await
+        }
+    }
+
+    pub struct HostDirHttps;
+
+    inventory::submit! {
+        &HostDirHttps as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for HostDirHttps {
+        fn segments(&self) -> &'static str {
+            "I serve the directory {dir} over https"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let dir = args.get_string("dir")?;
+
+            civ.proxies.clear();
+            host(&dir, true, civ).await
+        }
+    }
+
+    pub struct HostDirWithProxy;
+
+    inventory::submit! {
+        &HostDirWithProxy as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for HostDirWithProxy {
+        fn segments(&self) -> &'static str {
+            "I serve the directory {dir} and proxy {prefix} to {upstream}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let dir = args.get_string("dir")?;
+            let prefix = args.get_string("prefix")?;
+            let upstream = args.get_string("upstream")?;
+
+            if !upstream.starts_with("http://") {
+                return Err(ToolproofStepError::External(
+                    ToolproofInputError::StepError {
+                        reason: format!(
+                            "Requests can only be proxied to an http:// upstream, but received {upstream}"
+                        ),
+                    },
+                ));
+            }
+
+            // Proxies from earlier steps are kept, so that more than one prefix can be proxied
+            civ.proxies.push((prefix, upstream));
+
+            host(&dir, false, civ).await
         }
     }
 
@@ -93,10 +241,11 @@ mod host_dir {
         ) -> Result<(), ToolproofStepError> {
             let dir = args.get_string("dir")?;
 
-            host(&dir, civ).await?;
+            civ.proxies.clear();
+            host(&dir, false, civ).await?;
 
-            let url = format!("http://localhost:{}/", civ.ensure_port());
-            println!(
+            let url = format!("{}/", civ.server_origin());
+            log_println!(
                 "{}",
                 format!("----\nDirectory {dir} hosted at {url} for 60s\n----")
                     .yellow()
@@ -0,0 +1,312 @@
+use async_trait::async_trait;
+use reqwest::{header::CONTENT_TYPE, Method};
+use serde_json::{Map, Value};
+
+use super::{SegmentArgs, ToolproofInstruction, ToolproofRetriever};
+use crate::certificate::local_certificate;
+use crate::civilization::Civilization;
+use crate::errors::{
+    ToolproofInputError, ToolproofInternalError, ToolproofStepError, ToolproofTestFailure,
+};
+
+/// A request made directly, without a browser
+struct HttpRequest {
+    method: Method,
+    url: String,
+    body: Option<Value>,
+    headers: Map<String, Value>,
+    content_type: Option<String>,
+}
+
+impl HttpRequest {
+    /// Reads a request from the `url` argument, and the optional `method`, `body`,
+    /// `headers`, and `content_type` arguments.
+    /// URLs without a scheme are requested from the server hosted for this test.
+    fn from_args(
+        args: &SegmentArgs<'_>,
+        civ: &mut Civilization,
+    ) -> Result<Self, ToolproofStepError> {
+        let url = args.get_string("url")?;
+        let url = if url.starts_with("http://") || url.starts_with("https://") {
+            url
+        } else {
+            format!("{}{url}", civ.server_origin())
+        };
+
+        let method = args
+            .get_optional_string("method")?
+            .unwrap_or_else(|| "GET".to_string());
+        let method = Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|_| {
+            ToolproofInputError::StepError {
+                reason: format!("\"{method}\" is not a valid HTTP method"),
+            }
+        })?;
+
+        let headers = match args.get_optional_value("headers") {
+            Some(Value::Object(headers)) => headers,
+            Some(Value::Null) | None => Map::new(),
+            Some(_) => {
+                return Err(ToolproofInputError::StepError {
+                    reason: "headers should be an object of header names and values".to_string(),
+                }
+                .into())
+            }
+        };
+
+        Ok(Self {
+            method,
+            url,
+            body: args.get_optional_value("body"),
+            headers,
+            content_type: args.get_optional_string("content_type")?,
+        })
+    }
+
+    /// Sends the request, trusting the certificate that servers hosted over https
+    /// are served with alongside the usual root certificates
+    async fn send(self) -> Result<HttpResponse, ToolproofStepError> {
+        let client_err = |msg: String| ToolproofInternalError::Custom {
+            msg: format!("Failed to create an HTTP client: {msg}"),
+        };
+
+        let mut client = reqwest::Client::builder();
+        if let Ok(certificate) = local_certificate() {
+            let root = reqwest::Certificate::from_der(&certificate.ca_der)
+                .map_err(|e| client_err(e.to_string()))?;
+            client = client.add_root_certificate(root);
+        }
+        let client = client.build().map_err(|e| client_err(e.to_string()))?;
+
+        let mut request = client.request(self.method.clone(), &self.url);
+        let content_type = match self.body {
+            Some(Value::String(body)) => {
+                request = request.body(body);
+                self.content_type
+            }
+            // Other values are sent as JSON
+            Some(body) => {
+                request = request.body(body.to_string());
+                self.content_type
+                    .or_else(|| Some("application/json".to_string()))
+            }
+            None => self.content_type,
+        };
+        if let Some(content_type) = content_type {
+            request = request.header(CONTENT_TYPE, content_type);
+        }
+        for (name, value) in self.headers {
+            let value = match value {
+                Value::String(value) => value,
+                value => value.to_string(),
+            };
+            request = request.header(name, value);
+        }
+
+        let failed = |e: reqwest::Error| ToolproofTestFailure::Custom {
+            msg: format!("{} request to {} failed: {e}", self.method, self.url),
+        };
+
+        let response = request.send().await.map_err(failed)?;
+
+        let status = response.status().as_u16();
+        let mut headers = Map::new();
+        for (name, value) in response.headers() {
+            let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+            // Headers sent more than once are combined, as they would be by `fetch`
+            let combined = match headers.remove(name.as_str()) {
+                Some(Value::String(existing)) => format!("{existing}, {value}"),
+                _ => value,
+            };
+            headers.insert(name.to_string(), Value::String(combined));
+        }
+        let body = response.text().await.map_err(failed)?;
+
+        Ok(HttpResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+/// A response to a request made directly, without a browser
+pub struct HttpResponse {
+    pub status: u16,
+    /// Header values keyed by their lowercase names
+    pub headers: Map<String, Value>,
+    pub body: String,
+}
+
+mod request_url {
+    use super::*;
+
+    pub struct UrlStatus;
+
+    inventory::submit! {
+        &UrlStatus as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for UrlStatus {
+        fn segments(&self) -> &'static str {
+            "The status of {url}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let response = HttpRequest::from_args(args, civ)?.send().await?;
+
+            Ok(Value::from(response.status))
+        }
+    }
+
+    pub struct UrlBody;
+
+    inventory::submit! {
+        &UrlBody as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for UrlBody {
+        fn segments(&self) -> &'static str {
+            "The body of {url}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let response = HttpRequest::from_args(args, civ)?.send().await?;
+
+            Ok(Value::String(response.body))
+        }
+    }
+
+    pub struct UrlHeaders;
+
+    inventory::submit! {
+        &UrlHeaders as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for UrlHeaders {
+        fn segments(&self) -> &'static str {
+            "The headers of {url}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let response = HttpRequest::from_args(args, civ)?.send().await?;
+
+            Ok(Value::Object(response.headers))
+        }
+    }
+}
+
+mod request_builder {
+    use super::*;
+
+    pub struct MakeRequest;
+
+    inventory::submit! {
+        &MakeRequest as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for MakeRequest {
+        fn segments(&self) -> &'static str {
+            "I make a {method} request to {url}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let response = HttpRequest::from_args(args, civ)?.send().await?;
+            civ.last_response = Some(response);
+
+            Ok(())
+        }
+    }
+
+    fn last_response<'a>(civ: &'a Civilization) -> Result<&'a HttpResponse, ToolproofStepError> {
+        civ.last_response.as_ref().ok_or_else(|| {
+            ToolproofInputError::StepRequirementsNotMet {
+                reason: "no request has been made with \"I make a {method} request to {url}\""
+                    .to_string(),
+            }
+            .into()
+        })
+    }
+
+    pub struct ResponseStatus;
+
+    inventory::submit! {
+        &ResponseStatus as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for ResponseStatus {
+        fn segments(&self) -> &'static str {
+            "The response status"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            Ok(Value::from(last_response(civ)?.status))
+        }
+    }
+
+    pub struct ResponseBody;
+
+    inventory::submit! {
+        &ResponseBody as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for ResponseBody {
+        fn segments(&self) -> &'static str {
+            "The response body"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            Ok(Value::String(last_response(civ)?.body.clone()))
+        }
+    }
+
+    pub struct ResponseHeaders;
+
+    inventory::submit! {
+        &ResponseHeaders as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for ResponseHeaders {
+        fn segments(&self) -> &'static str {
+            "The response headers"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            Ok(Value::Object(last_response(civ)?.headers.clone()))
+        }
+    }
+}
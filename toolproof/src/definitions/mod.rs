@@ -4,8 +4,8 @@ use async_trait::async_trait;
 
 use crate::{
     civilization::Civilization,
-    errors::ToolproofStepError,
-    parser::parse_segments,
+    errors::{ToolproofInputError, ToolproofStepError},
+    parser::{parse_segments, ToolproofCustomStep},
     segments::{SegmentArgs, ToolproofSegments},
 };
 
@@ -13,6 +13,7 @@ mod assertions;
 pub mod browser;
 mod filesystem;
 mod hosting;
+pub mod http;
 mod process;
 
 /// Main instructions, generally start with "I ..."
@@ -94,6 +95,73 @@ pub fn register_assertions() -> HashMap<ToolproofSegments, &'static dyn Toolproo
     )
 }
 
+/// Adds user-defined retrievers and assertions alongside the builtin steps.
+/// These live for the remainder of the program, so are leaked to match the builtins.
+/// A custom step can't replace a builtin step, or another custom step, with the same segments.
+pub fn register_custom_steps(
+    custom_steps: Vec<ToolproofCustomStep>,
+    retrievers: &mut HashMap<ToolproofSegments, &'static dyn ToolproofRetriever>,
+    assertions: &mut HashMap<ToolproofSegments, &'static dyn ToolproofAssertion>,
+) -> Result<(), ToolproofInputError> {
+    use browser::custom_steps::{CustomAssertion, CustomRetriever};
+
+    // Which file each custom step came from, for naming both sides of a duplicate
+    let mut sources: HashMap<(&'static str, ToolproofSegments), String> = HashMap::new();
+    let duplicate = |kind: &str, step: &str, existing: Option<&String>, file_path: String| {
+        ToolproofInputError::DuplicateStep {
+            kind: kind.to_string(),
+            step: step.to_string(),
+            source_one: existing
+                .cloned()
+                .unwrap_or_else(|| format!("a builtin {kind}")),
+            source_two: file_path,
+        }
+    };
+
+    for custom_step in custom_steps {
+        match custom_step {
+            ToolproofCustomStep::Retriever {
+                segments,
+                js,
+                file_path,
+            } => {
+                let retriever: &'static CustomRetriever =
+                    Box::leak(Box::new(CustomRetriever::new(segments, js)?));
+                let segments = parse_segments(retriever.segments())?;
+                if retrievers.insert(segments.clone(), retriever).is_some() {
+                    return Err(duplicate(
+                        "retriever",
+                        retriever.segments(),
+                        sources.get(&("retriever", segments)),
+                        file_path,
+                    ));
+                }
+                sources.insert(("retriever", segments), file_path);
+            }
+            ToolproofCustomStep::Assertion {
+                segments,
+                js,
+                file_path,
+            } => {
+                let assertion: &'static CustomAssertion =
+                    Box::leak(Box::new(CustomAssertion::new(segments, js)?));
+                let segments = parse_segments(assertion.segments())?;
+                if assertions.insert(segments.clone(), assertion).is_some() {
+                    return Err(duplicate(
+                        "assertion",
+                        assertion.segments(),
+                        sources.get(&("assertion", segments)),
+                        file_path,
+                    ));
+                }
+                sources.insert(("assertion", segments), file_path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 
-use crate::civilization::Civilization;
-use crate::errors::ToolproofStepError;
+use crate::civilization::{parse_env_lines, Civilization};
+use crate::errors::{ToolproofInputError, ToolproofStepError};
 
 use super::{SegmentArgs, ToolproofInstruction, ToolproofRetriever};
 
@@ -33,6 +33,66 @@ mod env_var {
             Ok(())
         }
     }
+
+    pub struct LoadEnvFile;
+
+    inventory::submit! {
+        &LoadEnvFile as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for LoadEnvFile {
+        fn segments(&self) -> &'static str {
+            "I load environment variables from {filename}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let filename = args.get_string("filename")?;
+            let contents = civ.read_file(&filename)?;
+
+            let env_vars = parse_env_lines(&contents).map_err(|e| {
+                ToolproofStepError::External(ToolproofInputError::StepError {
+                    reason: format!("{filename} {e}"),
+                })
+            })?;
+            for (env_name, env_value) in env_vars {
+                civ.set_env(env_name, env_value);
+            }
+
+            Ok(())
+        }
+    }
+
+    pub struct GetEnvVar;
+
+    inventory::submit! {
+        &GetEnvVar as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for GetEnvVar {
+        fn segments(&self) -> &'static str {
+            "the environment variable {name}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            let env_name = args.get_string("name")?;
+
+            Ok(civ
+                .env_vars
+                .get(&env_name)
+                .map(|value| serde_json::Value::String(value.clone()))
+                .unwrap_or(serde_json::Value::Null))
+        }
+    }
 }
 
 mod run {
@@ -76,6 +136,55 @@ mod run {
         }
     }
 
+    pub struct QuietRun;
+
+    inventory::submit! {
+        &QuietRun as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for QuietRun {
+        fn segments(&self) -> &'static str {
+            "I run {command} with no stderr"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let command = args.get_string("command")?;
+
+            let exit_status = civ.run_command(command.to_string()).await?;
+
+            let stderr = civ
+                .last_command_output
+                .as_ref()
+                .map(|o| o.stderr.as_str())
+                .unwrap_or_default();
+
+            let problem = if !exit_status.success() {
+                format!("Failed to run command ({exit_status})")
+            } else if !stderr.trim().is_empty() {
+                "Command ran successfully, but wrote to stderr".to_string()
+            } else {
+                return Ok(());
+            };
+
+            Err(ToolproofTestFailure::Custom {
+                msg: format!(
+                    "{problem}\nCommand: {command}\nstdout:\n---\n{}\n---\nstderr:\n---\n{}\n---",
+                    civ.last_command_output
+                        .as_ref()
+                        .map(|o| o.stdout.as_str())
+                        .unwrap_or_else(|| "<empty>"),
+                    stderr,
+                ),
+            }
+            .into())
+        }
+    }
+
     pub struct FailingRun;
 
     inventory::submit! {
@@ -114,6 +223,87 @@ mod run {
     }
 }
 
+mod run_each {
+    use path_slash::PathExt;
+    use wax::Glob;
+
+    use crate::errors::ToolproofTestFailure;
+
+    use super::*;
+
+    pub struct RunForEachFile;
+
+    inventory::submit! {
+        &RunForEachFile as &dyn ToolproofInstruction
+    }
+
+    #[async_trait]
+    impl ToolproofInstruction for RunForEachFile {
+        fn segments(&self) -> &'static str {
+            "I run {command} for each file matching {glob}"
+        }
+
+        async fn run(
+            &self,
+            args: &SegmentArgs<'_>,
+            civ: &mut Civilization,
+        ) -> Result<(), ToolproofStepError> {
+            let command = args.get_string("command")?;
+            let glob = args.get_string("glob")?;
+
+            let matcher = Glob::new(&glob).map_err(|e| ToolproofInputError::StepError {
+                reason: format!("Invalid glob {glob}: {e}"),
+            })?;
+
+            let base_dir = civ.tmp_file_path(".");
+            let mut files: Vec<String> = matcher
+                .walk(&base_dir)
+                .flatten()
+                .filter(|entry| entry.file_type().is_file())
+                .filter_map(|entry| {
+                    entry
+                        .path()
+                        .strip_prefix(&base_dir)
+                        .ok()
+                        .map(|file| file.to_slash_lossy().into_owned())
+                })
+                .collect();
+            files.sort();
+
+            if files.is_empty() {
+                return Err(ToolproofTestFailure::Custom {
+                    msg: format!(
+                        "No files matched {glob} in the tree:\n-----\n{}\n-----",
+                        civ.get_file_tree()
+                    ),
+                }
+                .into());
+            }
+
+            let delim = &civ.universe.ctx.params.placeholder_delimiter;
+            let file_placeholder = format!("{delim}file{delim}");
+
+            for file in files {
+                let file_command = command.replace(&file_placeholder, &file);
+                let exit_status = civ.run_command(file_command.clone()).await?;
+
+                if !exit_status.success() {
+                    return Err(ToolproofTestFailure::Custom {
+                        msg: format!("Failed to run command for {file} ({})\nCommand: {file_command}\nstdout:\n---\n{}\n---\nstderr:\n---\n{}\n---",
+                        exit_status,
+                        civ.last_command_output.as_ref().map(|o| o.stdout.as_str()).unwrap_or_else(|| "<empty>"),
+                        civ.last_command_output.as_ref().map(|o| o.stderr.as_str()).unwrap_or_else(|| "<empty>"),
+                    ),
+                    }
+                    .into());
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
+
 mod stdio {
     use crate::errors::ToolproofTestFailure;
 
@@ -177,3 +367,49 @@ mod stdio {
         }
     }
 }
+
+mod system {
+    use super::*;
+
+    pub struct CurrentPlatform;
+
+    inventory::submit! {
+        &CurrentPlatform as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for CurrentPlatform {
+        fn segments(&self) -> &'static str {
+            "the current platform"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            Ok(std::env::consts::OS.into())
+        }
+    }
+
+    pub struct CurrentArchitecture;
+
+    inventory::submit! {
+        &CurrentArchitecture as &dyn ToolproofRetriever
+    }
+
+    #[async_trait]
+    impl ToolproofRetriever for CurrentArchitecture {
+        fn segments(&self) -> &'static str {
+            "the current architecture"
+        }
+
+        async fn run(
+            &self,
+            _args: &SegmentArgs<'_>,
+            _civ: &mut Civilization,
+        ) -> Result<serde_json::Value, ToolproofStepError> {
+            Ok(std::env::consts::ARCH.into())
+        }
+    }
+}
@@ -18,7 +18,10 @@
 use std::{borrow::Cow, time::Duration};
 
 use console::style;
-use similar::{Algorithm, ChangeTag, TextDiff};
+use similar::{Algorithm, ChangeTag, DiffOp, TextDiff};
+
+/// How many unchanged lines to show around each change when diffing values
+const VALUE_DIFF_CONTEXT_LINES: usize = 3;
 
 pub fn diff_snapshots(old: &str, new: &str) -> String {
     let newlines_matter = newlines_matter(old, new);
@@ -27,9 +30,33 @@ pub fn diff_snapshots(old: &str, new: &str) -> String {
         .timeout(Duration::from_millis(500))
         .diff_lines(old, new);
 
+    render_ops(&diff, diff.ops(), newlines_matter)
+}
+
+/// Diffs two values rendered as text, such as pretty-printed JSON,
+/// only showing the lines that changed and a few lines around them
+pub fn diff_values(old: &str, new: &str) -> String {
+    let newlines_matter = newlines_matter(old, new);
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Patience)
+        .timeout(Duration::from_millis(500))
+        .diff_lines(old, new);
+
+    diff.grouped_ops(VALUE_DIFF_CONTEXT_LINES)
+        .iter()
+        .map(|ops| render_ops(&diff, ops, newlines_matter))
+        .collect::<Vec<_>>()
+        .join(&format!("{:>5} {:>5} │{}\n", "", "", style("⋮").dim()))
+}
+
+fn render_ops<'a>(
+    diff: &'a TextDiff<'a, 'a, 'a, str>,
+    ops: &[DiffOp],
+    newlines_matter: bool,
+) -> String {
     let mut lines = vec![];
 
-    for op in diff.ops() {
+    for op in ops {
         for change in diff.iter_inline_changes(op) {
             match change.tag() {
                 ChangeTag::Insert => {
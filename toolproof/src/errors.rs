@@ -16,29 +16,66 @@ pub enum ToolproofInputError {
     },
     #[error("Argument \"{arg}\" requires a value, cannot be empty")]
     ArgumentRequiresValue { arg: String },
-    #[error("File {filename} failed to parse: {inner}")]
+    #[error("File {filename}{location} failed to parse: {message}")]
     ParseError {
         filename: String,
-        inner: serde_yaml::Error,
+        /// `:line:column` of the error within the file, if known
+        location: String,
+        message: String,
     },
     #[error("unclosed argument, expected a {expected} character")]
     UnclosedValue { expected: char },
     #[error("invalid path: \"{input}\"")]
     InvalidPath { input: String },
+    #[error("invalid JSON path: \"{input}\", {reason}")]
+    InvalidJsonPath { input: String, reason: String },
     #[error("duplicate name of \"{name}\" on the files {path_one} and {path_two}")]
     DuplicateName {
         path_one: String,
         path_two: String,
         name: String,
     },
+    #[error("duplicate {kind} \"{step}\" defined by both {source_one} and {source_two}")]
+    DuplicateStep {
+        kind: String,
+        step: String,
+        source_one: String,
+        source_two: String,
+    },
+    #[error("the custom_steps directory {path} does not exist")]
+    MissingCustomSteps { path: String },
     #[error("invalid reference: \"{input}\". (closest available: \"{closest}\")")]
     InvalidRef { input: String, closest: String },
+    #[error("invalid platform: \"{input}\". (closest available: \"{closest}\", expected one of: {available})")]
+    InvalidPlatform {
+        input: String,
+        closest: String,
+        available: String,
+    },
+    #[error("invalid arch: \"{input}\". (closest available: \"{closest}\", expected one of: {available})")]
+    InvalidArch {
+        input: String,
+        closest: String,
+        available: String,
+    },
+    #[error(
+        "extract steps need either an extract_location or an extract_placeholder: \"{extract}\""
+    )]
+    InvalidExtractTarget { extract: String },
+    #[error("snapshot steps can't be used in a nested list of steps: \"{snapshot}\"")]
+    NestedSnapshot { snapshot: String },
+    #[error("steps include themselves: {cycle}")]
+    IncludeCycle { cycle: String },
+    #[error("steps are nested more than {max_depth} levels deep: {chain}")]
+    NestingTooDeep { max_depth: usize, chain: String },
     #[error("step does not exist")]
     NonexistentStep,
     #[error("step requirements were not met: {reason}")]
     StepRequirementsNotMet { reason: String },
     #[error("{reason}")]
     StepError { reason: String },
+    #[error("only instructions can be setup steps, but this step is an assertion: \"{step}\"")]
+    SetupAssertion { step: String },
 }
 
 #[derive(Error, Debug)]
@@ -69,8 +106,17 @@ pub enum ToolproofStepError {
     Assertion(#[from] ToolproofTestFailure),
 }
 
+/// Failures in setup steps are called out, as the test didn't get as far as testing anything
+fn setup_label(step: &ToolproofTestStep) -> &'static str {
+    if step.is_setup() {
+        "setup "
+    } else {
+        ""
+    }
+}
+
 #[derive(Error, Debug)]
-#[error("Error in step \"{step}\":\n{arg_str}--\n{err}")]
+#[error("Error in {}step \"{step}\":\n{arg_str}--\n{err}", setup_label(.step))]
 pub struct ToolproofTestError {
     pub err: ToolproofStepError,
     pub step: ToolproofTestStep,
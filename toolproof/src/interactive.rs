@@ -1,14 +1,24 @@
-use std::{io, sync::Arc};
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+    sync::Arc,
+};
 
 use console::{Key, Term};
 use dialoguer::{theme::ColorfulTheme, Confirm, FuzzySelect, Select};
 use schematic::color::owo::OwoColorize;
 
 use crate::{
-    differ::diff_snapshots, errors::ToolproofInternalError, parser::ToolproofFileType,
-    universe::Universe, ToolproofTestFile,
+    closest_strings, differ::diff_snapshots, errors::ToolproofInternalError,
+    parser::ToolproofFileType, step_list::StepList, universe::Universe, ToolproofTestFile,
 };
 
+/// How many steps the step picker shows at once
+const STEP_PICKER_ROWS: usize = 10;
+
+/// Where the last test chosen in interactive mode is kept, relative to the working directory
+const LAST_RUN_FILE: &str = ".toolproof/last_run";
+
 #[derive(Debug)]
 pub enum RunMode {
     All,
@@ -39,13 +49,49 @@ impl From<io::Error> for ToolproofInternalError {
     }
 }
 
+/// Reads the last test chosen in interactive mode,
+/// if it was recorded and the test still exists
+fn load_last_run(universe: &Universe) -> Option<(String, &String)> {
+    let last_run =
+        std::fs::read_to_string(universe.ctx.working_directory.join(LAST_RUN_FILE)).ok()?;
+    let last_run = last_run.trim();
+
+    universe
+        .tests
+        .get(last_run)
+        .filter(|test| test.r#type == ToolproofFileType::Test)
+        .map(|test| (last_run.to_string(), &test.name))
+}
+
+/// Records the chosen test so it can be run again next time.
+/// This is only a convenience, so failing to write it is ignored.
+fn save_last_run(universe: &Universe, test_path: &str) {
+    let last_run_file = universe.ctx.working_directory.join(LAST_RUN_FILE);
+    if let Some(parent) = last_run_file.parent() {
+        _ = std::fs::create_dir_all(parent);
+    }
+    _ = std::fs::write(last_run_file, test_path);
+}
+
 pub fn get_run_mode(universe: &Arc<Universe>) -> Result<RunMode, ToolproofInternalError> {
     println!("{}\n", "Welcome to toolproof!".bold());
 
+    let last_run = load_last_run(universe);
+    let mut modes = vec!["All tests".to_string(), "Select test".to_string()];
+    if let Some((path, name)) = &last_run {
+        modes.insert(0, format!("Rerun last test: {path} ({name})"));
+    }
+
     let mode = Select::with_theme(&ColorfulTheme::default())
         .with_prompt("Which tests do you want to run?")
-        .items(&["All tests", "Select test"])
+        .items(&modes)
+        .default(0)
         .interact()?;
+    let mode = match last_run {
+        Some((path, _)) if mode == 0 => return Ok(RunMode::One(path)),
+        Some(_) => mode - 1,
+        None => mode,
+    };
     if mode == 0 {
         return Ok(RunMode::All);
     }
@@ -66,7 +112,10 @@ pub fn get_run_mode(universe: &Arc<Universe>) -> Result<RunMode, ToolproofIntern
         .items(&test_names)
         .interact()?;
 
-    Ok(RunMode::One(tests[test].0.clone()))
+    let test_path = tests[test].0.clone();
+    save_last_run(universe, &test_path);
+
+    Ok(RunMode::One(test_path))
 }
 
 pub fn question(s: impl AsRef<str>) -> Result<bool, ToolproofInternalError> {
@@ -151,3 +200,128 @@ pub fn confirm_snapshot(
 
     Ok(res)
 }
+
+/// Orders steps by how similar they are to the search, with steps containing
+/// the search first, returning indexes into `comparisons`
+fn rank_steps(search: &str, comparisons: &[String]) -> Vec<usize> {
+    let search = search.to_lowercase();
+    if search.is_empty() {
+        return (0..comparisons.len()).collect();
+    }
+
+    let mut ranked = closest_strings(&search, comparisons)
+        .into_iter()
+        .filter_map(|(s, _)| comparisons.iter().position(|c| std::ptr::eq(c, s)))
+        .collect::<Vec<_>>();
+    // Sorting is stable, so steps stay ordered by similarity within each group
+    ranked.sort_by_key(|i| !comparisons[*i].contains(&search));
+    ranked
+}
+
+/// Searches the available steps as the user types, then prints
+/// or copies the chosen step
+pub fn pick_step(
+    term: &Term,
+    steps: &StepList,
+    search: Option<&str>,
+) -> Result<(), ToolproofInternalError> {
+    let entries = steps.entries();
+    let comparisons = entries
+        .iter()
+        .map(|(_, step)| step.to_lowercase())
+        .collect::<Vec<_>>();
+
+    let mut search = search.unwrap_or_default().to_string();
+    let mut selected = 0;
+    let mut rendered_lines = 0;
+
+    let chosen = loop {
+        let ranked = rank_steps(&search, &comparisons);
+        let visible = ranked.len().min(STEP_PICKER_ROWS);
+        selected = selected.min(visible.saturating_sub(1));
+
+        term.clear_last_lines(rendered_lines)?;
+        term.write_line(&format!(
+            "{} {search}",
+            "Search steps (↑↓ to move, enter to choose, esc to quit):".bold()
+        ))?;
+        for (row, i) in ranked.iter().take(visible).enumerate() {
+            let (kind, step) = entries[*i];
+            if row == selected {
+                term.write_line(&format!("{} {} {}", "❯".cyan(), step.cyan(), kind.dimmed()))?;
+            } else {
+                term.write_line(&format!("  {step} {}", kind.dimmed()))?;
+            }
+        }
+        rendered_lines = visible + 1;
+
+        match term.read_key()? {
+            Key::Char(c) if !c.is_control() => {
+                search.push(c);
+                selected = 0;
+            }
+            Key::Backspace => {
+                search.pop();
+                selected = 0;
+            }
+            Key::ArrowUp => selected = selected.saturating_sub(1),
+            Key::ArrowDown => selected = (selected + 1).min(visible.saturating_sub(1)),
+            Key::Enter => break ranked.get(selected).map(|i| entries[*i].1),
+            Key::Escape => break None,
+            _ => {}
+        }
+    };
+
+    term.clear_last_lines(rendered_lines)?;
+    let Some(step) = chosen else {
+        return Ok(());
+    };
+
+    let action = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!("What do you want to do with \"{step}\"?"))
+        .items(&["Print it", "Copy it to my clipboard"])
+        .interact()?;
+
+    if action == 1 {
+        if copy_to_clipboard(step) {
+            println!("{}", "Copied the step to your clipboard".green().bold());
+            return Ok(());
+        }
+        println!(
+            "{}",
+            "Couldn't find a clipboard to copy to, so printing the step instead:".yellow()
+        );
+    }
+    println!("{step}");
+
+    Ok(())
+}
+
+/// Copies text using the platform's clipboard command, returning whether one succeeded
+fn copy_to_clipboard(text: &str) -> bool {
+    let commands: &[(&str, &[&str])] = if cfg!(target_os = "macos") {
+        &[("pbcopy", &[])]
+    } else if cfg!(target_os = "windows") {
+        &[("clip", &[])]
+    } else {
+        &[("wl-copy", &[]), ("xclip", &["-selection", "clipboard"])]
+    };
+
+    commands.iter().any(|(program, args)| {
+        let Ok(mut child) = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            return false;
+        };
+
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        child.wait().is_ok_and(|status| status.success()) && written
+    })
+}
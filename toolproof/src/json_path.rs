@@ -0,0 +1,180 @@
+use std::fmt::Display;
+
+use serde_json::Value;
+
+use crate::errors::ToolproofInputError;
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A path to a value nested inside of another value, such as `$.users[0].name`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    segments: Vec<JsonPathSegment>,
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "$")?;
+        for segment in &self.segments {
+            match segment {
+                JsonPathSegment::Key(key) => write!(f, ".{key}")?,
+                JsonPathSegment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl JsonPath {
+    /// Parses keys separated by `.` and array indexes in brackets,
+    /// optionally starting from `$`. Keys containing `.` or `[` can be
+    /// quoted inside brackets, such as `$["file.name"]`.
+    pub fn parse(input: &str) -> Result<Self, ToolproofInputError> {
+        let invalid = |reason: &str| ToolproofInputError::InvalidJsonPath {
+            input: input.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let trimmed = input.trim();
+        let with_dot;
+        let mut rest = if let Some(rest) = trimmed.strip_prefix('$') {
+            rest
+        } else if trimmed.is_empty() || trimmed.starts_with(['.', '[']) {
+            trimmed
+        } else {
+            // Paths without a `$` can start with a bare key
+            with_dot = format!(".{trimmed}");
+            &with_dot
+        };
+
+        let mut segments = vec![];
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(invalid("expected a key after \".\""));
+                }
+                segments.push(JsonPathSegment::Key(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let Some(end) = after.find(']') else {
+                    return Err(invalid("expected a closing \"]\""));
+                };
+                let inner = after[..end].trim();
+                let quoted = ['"', '\''].into_iter().find_map(|quote| {
+                    inner
+                        .strip_prefix(quote)
+                        .and_then(|key| key.strip_suffix(quote))
+                });
+                match (quoted, inner.parse::<usize>()) {
+                    (Some(key), _) => segments.push(JsonPathSegment::Key(key.to_string())),
+                    (None, Ok(index)) => segments.push(JsonPathSegment::Index(index)),
+                    (None, Err(_)) => {
+                        return Err(invalid(
+                            "brackets must contain an array index or a quoted key",
+                        ))
+                    }
+                }
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid("expected \".\" or \"[\" between segments"));
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Walks the path through a value, returning the value it points to
+    pub fn select<'a>(&self, value: &'a Value) -> Result<&'a Value, String> {
+        let mut current = value;
+        for (i, segment) in self.segments.iter().enumerate() {
+            let walked = JsonPath {
+                segments: self.segments[..i].to_vec(),
+            };
+            current = match (segment, current) {
+                (JsonPathSegment::Key(key), Value::Object(map)) => {
+                    map.get(key).ok_or_else(|| {
+                        format!(
+                            "The path {self} does not exist, as {walked} has no key \"{key}\" \
+                            (has {})",
+                            map.keys()
+                                .map(|k| format!("\"{k}\""))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        )
+                    })?
+                }
+                (JsonPathSegment::Index(index), Value::Array(items)) => {
+                    items.get(*index).ok_or_else(|| {
+                        format!(
+                            "The path {self} does not exist, as {walked} has {} items",
+                            items.len()
+                        )
+                    })?
+                }
+                (_, other) => {
+                    let expected = match segment {
+                        JsonPathSegment::Key(_) => "an object",
+                        JsonPathSegment::Index(_) => "an array",
+                    };
+                    return Err(format!(
+                        "The path {self} does not exist, as {walked} is not {expected}: {other}"
+                    ));
+                }
+            };
+        }
+        Ok(current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_parsing_json_paths() {
+        let path = JsonPath::parse("$.users[1].name").unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                JsonPathSegment::Key("users".to_string()),
+                JsonPathSegment::Index(1),
+                JsonPathSegment::Key("name".to_string()),
+            ]
+        );
+        assert_eq!(JsonPath::parse("users[1].name").unwrap(), path);
+        assert_eq!(
+            JsonPath::parse("$['file.name']").unwrap().segments,
+            vec![JsonPathSegment::Key("file.name".to_string())]
+        );
+        assert_eq!(JsonPath::parse("$").unwrap().segments, vec![]);
+
+        assert!(JsonPath::parse("$.users.").is_err());
+        assert!(JsonPath::parse("$.users[one]").is_err());
+        assert!(JsonPath::parse("$.users[1").is_err());
+        assert!(JsonPath::parse("$users").is_err());
+    }
+
+    #[test]
+    fn test_selecting_json_paths() {
+        let value = json!({ "users": [{ "name": "Ada" }, { "name": "Grace" }] });
+
+        let path = JsonPath::parse("$.users[1].name").unwrap();
+        assert_eq!(path.select(&value), Ok(&json!("Grace")));
+
+        let path = JsonPath::parse("$.users[2].name").unwrap();
+        assert_eq!(
+            path.select(&value),
+            Err("The path $.users[2].name does not exist, as $.users has 2 items".to_string())
+        );
+
+        let path = JsonPath::parse("$.users.name").unwrap();
+        assert!(path.select(&value).is_err());
+    }
+}
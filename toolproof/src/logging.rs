@@ -1,7 +1,79 @@
+use std::{
+    borrow::Cow,
+    fs::{self, File},
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+};
+
 use console::style;
 
 use crate::{ToolproofTestStep, ToolproofTestStepState};
 
+static LOG_FILE: OnceLock<Mutex<File>> = OnceLock::new();
+static COLORS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turns off styling, both for `console` styles and for the `owo` colors
+/// printed through `log_println!` and `log_eprintln!`
+pub fn disable_colors() {
+    COLORS_ENABLED.store(false, Ordering::Relaxed);
+    console::set_colors_enabled(false);
+    console::set_colors_enabled_stderr(false);
+}
+
+/// The output as it should be printed to the terminal
+pub fn console_output(output: &str) -> Cow<'_, str> {
+    if COLORS_ENABLED.load(Ordering::Relaxed) {
+        Cow::Borrowed(output)
+    } else {
+        Cow::Owned(strip_ansi_escapes::strip_str(output))
+    }
+}
+
+/// Mirrors all output printed through `log_println!` and `log_eprintln!` to a file
+pub fn init_log_file(path: &Path) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = File::create(path)?;
+    _ = LOG_FILE.set(Mutex::new(file));
+    Ok(())
+}
+
+/// Writes a line to the log file (if one was configured), stripped of any styling
+pub fn write_log_file(output: &str) {
+    let Some(file) = LOG_FILE.get() else {
+        return;
+    };
+    if let Ok(mut file) = file.lock() {
+        _ = writeln!(file, "{}", strip_ansi_escapes::strip_str(output));
+    }
+}
+
+/// `println!`, which is also written to the `--log-file`
+macro_rules! log_println {
+    ($($arg:tt)*) => {{
+        let output = format!($($arg)*);
+        println!("{}", $crate::logging::console_output(&output));
+        $crate::logging::write_log_file(&output);
+    }};
+}
+
+/// `eprintln!`, which is also written to the `--log-file`
+macro_rules! log_eprintln {
+    ($($arg:tt)*) => {{
+        let output = format!($($arg)*);
+        eprintln!("{}", $crate::logging::console_output(&output));
+        $crate::logging::write_log_file(&output);
+    }};
+}
+
+pub(crate) use log_eprintln;
+pub(crate) use log_println;
+
 pub fn log_step_runs(steps: &Vec<ToolproofTestStep>, indent: usize) {
     for step in steps {
         use ToolproofTestStepState::*;
@@ -11,11 +83,14 @@ pub fn log_step_runs(steps: &Vec<ToolproofTestStep>, indent: usize) {
             "".to_string()
         };
 
-        println!(
+        log_println!(
             "{prefix}{}",
             match step.state() {
                 Dormant => style(format!("⦸ {step}")).dim(),
-                Skipped => style(format!("⊝ {step}")).dim(),
+                Skipped => match step.skip_reason() {
+                    Some(reason) => style(format!("⊝ {step} (skipped: {reason})")).dim(),
+                    None => style(format!("⊝ {step}")).dim(),
+                },
                 Failed => style(format!("✘ {step}")).red(),
                 Passed => style(format!("✓ {step}")).green(),
             }
@@ -5,58 +5,81 @@ use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
 use std::time::Duration;
-use std::{collections::HashMap, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
 
 use console::{style, Term};
 use futures::future::join_all;
 use miette::IntoDiagnostic;
 use normalize_path::NormalizePath;
-use parser::{parse_macro, ToolproofFileType, ToolproofPlatform};
+use parser::{parse_custom_step, parse_macro, ToolproofArch, ToolproofFileType, ToolproofPlatform};
 use schematic::color::owo::OwoColorize;
 use segments::ToolproofSegments;
 use semver::{Version, VersionReq};
-use similar_string::compare_similarity;
+use similar_string::{compare_similarity, find_best_similarity};
 use tokio::fs::read_to_string;
 use tokio::process::Command;
 use tokio::sync::OnceCell;
 use wax::Glob;
 
-use crate::definitions::{register_assertions, register_instructions, register_retrievers};
+use crate::cache::ParsedFileCache;
+use crate::civilization::parse_env_lines;
+use crate::definitions::{
+    register_assertions, register_custom_steps, register_instructions, register_retrievers,
+};
 use crate::differ::diff_snapshots;
 use crate::errors::{ToolproofInputError, ToolproofStepError, ToolproofTestError};
-use crate::interactive::{confirm_snapshot, get_run_mode, question, RunMode};
-use crate::logging::log_step_runs;
-use crate::options::configure;
+use crate::interactive::{confirm_snapshot, get_run_mode, pick_step, question, RunMode};
+use crate::json_path::JsonPath;
+use crate::logging::{disable_colors, init_log_file, log_eprintln, log_println, log_step_runs};
+use crate::options::{configure, ToolproofParams};
 use crate::parser::parse_segments;
+use crate::platforms::{match_line_endings, normalize_line_endings};
+use crate::progress::RunProgress;
+use crate::scaffold::scaffold_test;
+use crate::step_list::StepList;
 use crate::universe::Universe;
-use crate::{
-    parser::parse_file, runner::run_toolproof_experiment, snapshot_writer::write_yaml_snapshots,
-};
+use crate::{runner::run_toolproof_experiment, snapshot_writer::write_yaml_snapshots};
 
+mod cache;
+mod certificate;
 mod civilization;
 mod definitions;
 mod differ;
 mod errors;
 mod interactive;
+mod json_path;
 mod logging;
 mod options;
 mod parser;
 mod platforms;
+mod progress;
 mod runner;
+mod scaffold;
 mod segments;
 mod snapshot_writer;
+mod step_list;
 mod universe;
 
-#[derive(Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ToolproofTestFile {
     pub name: String,
     r#type: ToolproofFileType,
     pub platforms: Option<Vec<ToolproofPlatform>>,
+    pub arch: Option<Vec<ToolproofArch>>,
+    pub skip: Option<String>,
+    /// Known-flaky tests, whose failures are reported but don't fail the run
+    pub quarantine: bool,
     pub steps: Vec<ToolproofTestStep>,
     pub original_source: String,
     pub file_path: String,
     pub file_directory: String,
     pub failure_screenshot: Option<PathBuf>,
+    pub kept_temp_dir: Option<PathBuf>,
+    /// Screenshots that didn't match their baseline image, as `(baseline, actual)` paths
+    pub changed_images: Vec<(PathBuf, PathBuf)>,
 }
 
 #[derive(Debug, Clone)]
@@ -75,7 +98,7 @@ pub enum ToolproofTestSuccess {
     Passed { attempts: usize },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 pub enum ToolproofTestStepState {
     Dormant,
     Skipped,
@@ -83,14 +106,26 @@ pub enum ToolproofTestStepState {
     Passed,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Where an extract step stores the value it retrieves
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub enum ToolproofExtractTarget {
+    /// A file, relative to the temporary directory
+    File(String),
+    /// A placeholder available to later steps
+    Placeholder(String),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 pub enum ToolproofTestStep {
     Ref {
         other_file: String,
+        args: HashMap<String, serde_json::Value>,
         orig: String,
         hydrated_steps: Option<Vec<ToolproofTestStep>>,
         state: ToolproofTestStepState,
         platforms: Option<Vec<ToolproofPlatform>>,
+        arch: Option<Vec<ToolproofArch>>,
+        skip: Option<String>,
     },
     Macro {
         step_macro: ToolproofSegments,
@@ -99,6 +134,8 @@ pub enum ToolproofTestStep {
         hydrated_steps: Option<Vec<ToolproofTestStep>>,
         state: ToolproofTestStepState,
         platforms: Option<Vec<ToolproofPlatform>>,
+        arch: Option<Vec<ToolproofArch>>,
+        skip: Option<String>,
     },
     Instruction {
         step: ToolproofSegments,
@@ -106,14 +143,25 @@ pub enum ToolproofTestStep {
         orig: String,
         state: ToolproofTestStepState,
         platforms: Option<Vec<ToolproofPlatform>>,
+        arch: Option<Vec<ToolproofArch>>,
+        skip: Option<String>,
+        /// Overrides the global `timeout` for this step, in seconds
+        timeout: Option<u64>,
+        /// Whether this step only prepares the test, such as serving a directory
+        setup: bool,
     },
     Assertion {
         retrieval: ToolproofSegments,
-        assertion: ToolproofSegments,
+        /// One or more assertions, chained with "and", that all run against the retrieved value
+        assertions: Vec<ToolproofSegments>,
         args: HashMap<String, serde_json::Value>,
         orig: String,
         state: ToolproofTestStepState,
         platforms: Option<Vec<ToolproofPlatform>>,
+        arch: Option<Vec<ToolproofArch>>,
+        skip: Option<String>,
+        /// Overrides the global `timeout` for this step, in seconds
+        timeout: Option<u64>,
     },
     Snapshot {
         snapshot: ToolproofSegments,
@@ -122,14 +170,24 @@ pub enum ToolproofTestStep {
         orig: String,
         state: ToolproofTestStepState,
         platforms: Option<Vec<ToolproofPlatform>>,
+        arch: Option<Vec<ToolproofArch>>,
+        skip: Option<String>,
+        /// Overrides the global `timeout` for this step, in seconds
+        timeout: Option<u64>,
     },
     Extract {
         extract: ToolproofSegments,
-        extract_location: String,
+        extract_target: ToolproofExtractTarget,
+        /// Narrows the retrieved value down to one of its fields before writing it
+        extract_path: Option<JsonPath>,
         args: HashMap<String, serde_json::Value>,
         orig: String,
         state: ToolproofTestStepState,
         platforms: Option<Vec<ToolproofPlatform>>,
+        arch: Option<Vec<ToolproofArch>>,
+        skip: Option<String>,
+        /// Overrides the global `timeout` for this step, in seconds
+        timeout: Option<u64>,
     },
 }
 
@@ -190,25 +248,164 @@ impl ToolproofTestStep {
             | Snapshot { state, .. } => state.clone(),
         }
     }
+
+    pub fn set_state(&mut self, new_state: ToolproofTestStepState) {
+        use ToolproofTestStep::*;
+
+        match self {
+            Ref { state, .. }
+            | Macro { state, .. }
+            | Instruction { state, .. }
+            | Assertion { state, .. }
+            | Extract { state, .. }
+            | Snapshot { state, .. } => *state = new_state,
+        }
+    }
+
+    /// The timeout set on this step, if it overrides the global `timeout`
+    pub fn timeout(&self) -> Option<u64> {
+        use ToolproofTestStep::*;
+
+        match self {
+            Ref { .. } | Macro { .. } => None,
+            Instruction { timeout, .. }
+            | Assertion { timeout, .. }
+            | Extract { timeout, .. }
+            | Snapshot { timeout, .. } => *timeout,
+        }
+    }
+
+    /// Whether this step only prepares the test, so failing it doesn't leave
+    /// any browser state worth capturing
+    pub fn is_setup(&self) -> bool {
+        matches!(self, ToolproofTestStep::Instruction { setup: true, .. })
+    }
+
+    pub fn skip_reason(&self) -> Option<&String> {
+        use ToolproofTestStep::*;
+
+        match self {
+            Ref { skip, .. }
+            | Macro { skip, .. }
+            | Instruction { skip, .. }
+            | Assertion { skip, .. }
+            | Extract { skip, .. }
+            | Snapshot { skip, .. } => skip.as_ref(),
+        }
+    }
 }
 
-fn closest_strings<'o>(target: &String, options: &'o Vec<String>) -> Vec<(&'o String, f64)> {
+fn closest_strings<'o>(target: &str, options: &'o [String]) -> Vec<(&'o String, f64)> {
     let mut scores = options
         .iter()
         .map(|s| (s, compare_similarity(target, s)))
         .collect::<Vec<_>>();
 
     scores.sort_by(|a, b| {
-        b.partial_cmp(a)
+        b.1.partial_cmp(&a.1)
             .expect("similarities should not be NaN or Infinity")
     });
 
     scores
 }
 
+/// Picks which of the closest steps to suggest, in order of similarity. The closest step
+/// is always suggested, then up to `suggestion_limit` steps that score at least
+/// `suggestion_threshold`, and beyond that only steps that score at least `suggestion_close_match`.
+fn suggested_strings<'o>(
+    matches: Vec<(&'o String, f64)>,
+    params: &ToolproofParams,
+) -> Vec<&'o String> {
+    matches
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (_, score))| {
+            if *i >= params.suggestion_limit && *score < params.suggestion_close_match {
+                false
+            } else {
+                *i == 0 || *score >= params.suggestion_threshold
+            }
+        })
+        .map(|(_, (s, _))| s)
+        .collect()
+}
+
+/// Checks that every `ref` step points at a loaded file,
+/// so that a broken reference is caught before any tests run
+fn validate_refs(
+    file_directory: &str,
+    steps: &[ToolproofTestStep],
+    tests: &BTreeMap<String, ToolproofTestFile>,
+    errors: &mut Vec<ToolproofInputError>,
+) {
+    for step in steps {
+        let ToolproofTestStep::Ref { other_file, .. } = step else {
+            continue;
+        };
+
+        let target_path = PathBuf::from(file_directory)
+            .join(other_file)
+            .normalize()
+            .to_string_lossy()
+            .into_owned();
+        if !tests.contains_key(&target_path) {
+            let avail = tests.keys().collect::<Vec<_>>();
+            let closest = find_best_similarity(&target_path, &avail).map(|s| s.0);
+            errors.push(ToolproofInputError::InvalidRef {
+                input: target_path,
+                closest: closest.unwrap_or_else(|| "<nothing found>".to_string()),
+            });
+        }
+    }
+}
+
+/// The point at which the whole run is cancelled, if `max_run_duration` is set
+struct RunDeadline {
+    at: Option<tokio::time::Instant>,
+    secs: u64,
+}
+
+impl RunDeadline {
+    fn new(max_run_duration: Option<u64>) -> Self {
+        Self {
+            at: max_run_duration
+                .map(|secs| tokio::time::Instant::now() + Duration::from_secs(secs)),
+            secs: max_run_duration.unwrap_or_default(),
+        }
+    }
+
+    /// Resolves once the deadline has passed, or never if there is no deadline
+    async fn reached(&self) {
+        match self.at {
+            Some(at) => tokio::time::sleep_until(at).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn remaining(&self) -> Duration {
+        match self.at {
+            Some(at) => at.saturating_duration_since(tokio::time::Instant::now()),
+            None => Duration::MAX,
+        }
+    }
+
+    fn log_exceeded(&self) {
+        log_eprintln!(
+            "\n{}",
+            format!(
+                "Exceeded the maximum run duration of {}s, cancelling outstanding tests...",
+                self.secs
+            )
+            .red()
+            .bold()
+        );
+    }
+}
+
 async fn acquire_or_shutdown<T>(
     semaphore: &Arc<tokio::sync::Semaphore>,
     shutdown_rx: &tokio::sync::watch::Receiver<bool>,
+    deadline: &RunDeadline,
     in_flight: &[tokio::task::JoinHandle<T>],
 ) -> Result<tokio::sync::OwnedSemaphorePermit, ()> {
     let mut shutdown_check = shutdown_rx.clone();
@@ -217,7 +414,14 @@ async fn acquire_or_shutdown<T>(
             for h in in_flight {
                 h.abort();
             }
-            eprintln!("\n{}", "Interrupted, shutting down...".yellow().bold());
+            log_eprintln!("\n{}", "Interrupted, shutting down...".yellow().bold());
+            Err(())
+        }
+        _ = deadline.reached() => {
+            for h in in_flight {
+                h.abort();
+            }
+            deadline.log_exceeded();
             Err(())
         }
         permit = semaphore.clone().acquire_owned() => Ok(permit.unwrap()),
@@ -227,6 +431,7 @@ async fn acquire_or_shutdown<T>(
 async fn join_or_shutdown<T>(
     hands: Vec<tokio::task::JoinHandle<T>>,
     shutdown_rx: &tokio::sync::watch::Receiver<bool>,
+    deadline: &RunDeadline,
 ) -> Result<Vec<Result<T, tokio::task::JoinError>>, ()> {
     let abort_handles: Vec<_> = hands.iter().map(|h| h.abort_handle()).collect();
     let mut shutdown_check = shutdown_rx.clone();
@@ -235,47 +440,143 @@ async fn join_or_shutdown<T>(
             for h in &abort_handles {
                 h.abort();
             }
-            eprintln!("\n{}", "Interrupted, shutting down...".yellow().bold());
+            log_eprintln!("\n{}", "Interrupted, shutting down...".yellow().bold());
             Err(())
         }
-        results = join_all(hands) => Ok(results),
+        results = tokio::time::timeout(deadline.remaining(), join_all(hands)) => match results {
+            Ok(results) => Ok(results),
+            Err(_) => {
+                for h in &abort_handles {
+                    h.abort();
+                }
+                deadline.log_exceeded();
+                Err(())
+            }
+        },
     }
 }
 
+/// The directories to discover test and macro files within
+fn discovery_roots(params: &ToolproofParams) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = params
+        .root
+        .iter()
+        .chain(params.roots.iter())
+        .cloned()
+        .collect();
+    if roots.is_empty() {
+        roots.push(".".into());
+    }
+    roots
+}
+
+/// Finds the files matching a glob within every root, skipping any ignored paths
+/// and only including each file once if the roots overlap
+fn walk_roots(
+    glob: &Glob,
+    roots: &[PathBuf],
+    ignore: &[String],
+) -> Result<Vec<PathBuf>, wax::BuildError> {
+    let mut seen = HashSet::new();
+    let mut files = vec![];
+
+    for root in roots {
+        // Negations are applied while walking, so ignored directories are never read
+        let walker = glob.walk(root).not(ignore.iter().map(String::as_str))?;
+        for entry in walker.flatten() {
+            let file = entry.path().to_path_buf();
+            if seen.insert(file.normalize()) {
+                files.push(file);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Reads files concurrently, but only `concurrency` files at a time,
+/// so that large suites don't run out of open file handles
+async fn read_files(
+    files: Vec<PathBuf>,
+    concurrency: usize,
+) -> Vec<(PathBuf, std::io::Result<String>)> {
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(concurrency.max(1)));
+
+    let loaded_files = files.into_iter().map(|file| {
+        let semaphore = Arc::clone(&semaphore);
+        async move {
+            let _permit = semaphore
+                .acquire()
+                .await
+                .expect("semaphore is never closed");
+            let contents = read_to_string(&file).await;
+            (file, contents)
+        }
+    });
+
+    join_all(loaded_files).await
+}
+
 async fn main_inner() -> Result<(), ()> {
-    let ctx = configure();
+    let mut ctx = configure();
+
+    if ctx.params.no_color {
+        disable_colors();
+    }
+
+    if let Some(log_file) = &ctx.params.log_file {
+        if let Err(e) = init_log_file(log_file) {
+            eprintln!("Failed to create log file {}: {e}", log_file.display());
+            return Err(());
+        }
+    }
 
     if let Some(versions) = &ctx.params.supported_versions {
         let req = VersionReq::parse(versions).into_diagnostic().map_err(|e| {
-            eprintln!("Failed to parse supported versions: {e:?}");
+            log_eprintln!("Failed to parse supported versions: {e:?}");
         })?;
         let active = Version::parse(&ctx.version).expect("Crate version should be valid");
         let is_local = ctx.version == "0.0.0";
 
         if !req.matches(&active) && !is_local {
-            eprintln!(
+            log_eprintln!(
                 "Toolproof is running version {}, but your configuration requires Toolproof {}",
-                ctx.version, versions
+                ctx.version,
+                versions
             );
             return Err(());
         }
     }
 
-    if ctx.params.skip_hooks {
-        println!("{}", "Skipping before_all commands".yellow().bold());
+    if let Some(name) = &ctx.params.new_test {
+        let path = scaffold_test(&ctx.params, name).map_err(|e| log_eprintln!("{e}"))?;
+        log_println!("Created {}", path.display());
+        return Ok(());
+    }
+
+    if ctx.params.print_test_names || ctx.params.list_steps {
+        // Test names are printed for completion scripts, so hooks shouldn't run or log
+    } else if ctx.params.skip_hooks {
+        log_println!("{}", "Skipping before_all commands".yellow().bold());
     } else {
+        // Commands can write KEY=VALUE lines to this file to set environment variables for every test
+        let env_file = tempfile::NamedTempFile::new().map_err(|e| {
+            log_eprintln!("Failed to create the $TOOLPROOF_ENV file for before_all commands: {e}")
+        })?;
+
         for before in &ctx.params.before_all {
             let before_cmd = &before.command;
             let mut command = Command::new("sh");
             command
                 .arg("-c")
                 .current_dir(&ctx.working_directory)
+                .env("TOOLPROOF_ENV", env_file.path())
                 .arg(before_cmd);
 
             command.stdout(Stdio::piped());
             command.stderr(Stdio::piped());
 
-            println!(
+            log_println!(
                 "{}{}",
                 "Running before_all command: ".blue().bold(),
                 before_cmd.cyan().bold(),
@@ -283,7 +584,7 @@ async fn main_inner() -> Result<(), ()> {
 
             let running = command
                 .spawn()
-                .map_err(|_| eprintln!("Failed to run command: {before_cmd}"))?;
+                .map_err(|_| log_eprintln!("Failed to run command: {before_cmd}"))?;
 
             let Ok(_) =
                 (match tokio::time::timeout(Duration::from_secs(300), running.wait_with_output())
@@ -291,34 +592,47 @@ async fn main_inner() -> Result<(), ()> {
                 {
                     Ok(out) => out,
                     Err(_) => {
-                        eprintln!("Failed to run command due to timeout: {before_cmd}");
+                        log_eprintln!("Failed to run command due to timeout: {before_cmd}");
                         return Err(());
                     }
                 })
             else {
-                eprintln!("Failed to run command: {before_cmd}");
+                log_eprintln!("Failed to run command: {before_cmd}");
                 return Err(());
             };
         }
+
+        let env_vars = std::fs::read_to_string(env_file.path())
+            .map_err(|e| e.to_string())
+            .and_then(|contents| parse_env_lines(&contents))
+            .map_err(|e| {
+                log_eprintln!(
+                    "Failed to read the $TOOLPROOF_ENV file written by before_all commands: {e}"
+                )
+            })?;
+        ctx.params.env.extend(env_vars);
     }
 
     let start = Instant::now();
 
     let mut errors = vec![];
 
-    let macro_glob = Glob::new("**/*.toolproof.macro.yml").expect("Valid glob");
-    let macro_walker = macro_glob
-        .walk(ctx.params.root.clone().unwrap_or(".".into()))
-        .flatten();
+    let roots = discovery_roots(&ctx.params);
 
-    let loaded_macros = macro_walker
-        .map(|entry| {
-            let file = entry.path().to_path_buf();
-            async { (file.clone(), read_to_string(file).await) }
-        })
-        .collect::<Vec<_>>();
+    let macro_glob = Glob::new(&ctx.params.macro_glob).map_err(|e| {
+        log_eprintln!("Invalid macro_glob \"{}\": {e}", ctx.params.macro_glob);
+    })?;
+    let glob = Glob::new(&ctx.params.test_glob).map_err(|e| {
+        log_eprintln!("Invalid test_glob \"{}\": {e}", ctx.params.test_glob);
+    })?;
+
+    let ignore = &ctx.params.ignore;
+    let macro_files = walk_roots(&macro_glob, &roots, ignore).map_err(|e| {
+        log_eprintln!("Invalid ignore glob in [{}]: {e}", ignore.join(", "));
+    })?;
+    let macro_paths: HashSet<_> = macro_files.iter().map(|file| file.normalize()).collect();
 
-    let macros = join_all(loaded_macros).await;
+    let macros = read_files(macro_files, ctx.params.concurrency).await;
 
     let all_macros: HashMap<_, _> = macros
         .into_iter()
@@ -331,26 +645,51 @@ async fn main_inner() -> Result<(), ()> {
         })
         .collect();
 
-    let glob = Glob::new("**/*.toolproof.yml").expect("Valid glob");
-    let walker = glob
-        .walk(ctx.params.root.clone().unwrap_or(".".into()))
-        .flatten();
+    let mut custom_steps = vec![];
+    if let Some(custom_steps_dir) = &ctx.params.custom_steps {
+        if !custom_steps_dir.is_dir() {
+            errors.push(ToolproofInputError::MissingCustomSteps {
+                path: custom_steps_dir.to_string_lossy().into_owned(),
+            });
+        }
 
-    let loaded_files = walker
-        .map(|entry| {
-            let file = entry.path().to_path_buf();
-            async { (file.clone(), read_to_string(file).await) }
-        })
+        let custom_step_glob = Glob::new("**/*.toolproof.step.yml").expect("Valid glob");
+        let custom_step_files = custom_step_glob
+            .walk(custom_steps_dir)
+            .flatten()
+            .map(|entry| entry.path().to_path_buf())
+            .collect::<Vec<_>>();
+
+        for (p, i) in read_files(custom_step_files, ctx.params.concurrency).await {
+            match parse_custom_step(&i.unwrap(), p) {
+                Ok(step) => custom_steps.push(step),
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+
+    // A broad test glob could also match macro files, which shouldn't be run as tests
+    let loaded_files = walk_roots(&glob, &roots, ignore)
+        .map_err(|e| {
+            log_eprintln!("Invalid ignore glob in [{}]: {e}", ignore.join(", "));
+        })?
+        .into_iter()
+        .filter(|file| !macro_paths.contains(&file.normalize()))
         .collect::<Vec<_>>();
 
-    let files = join_all(loaded_files).await;
+    let files = read_files(loaded_files, ctx.params.concurrency).await;
 
     let mut names_thus_far: Vec<(String, String)> = vec![];
+    let mut parsed_file_cache = ParsedFileCache::load(
+        &ctx.working_directory,
+        ctx.version,
+        !ctx.params.no_parse_cache,
+    );
 
     let all_tests: BTreeMap<_, _> = files
         .into_iter()
         .filter_map(|(p, i)| {
-            let test_file = match parse_file(&i.unwrap(), p.clone()) {
+            let test_file = match parsed_file_cache.parse_file(&i.unwrap(), p.clone()) {
                 Ok(f) => {
                     if let Some((_, other_path)) = names_thus_far.iter().find(|(n, _)| *n == f.name)
                     {
@@ -372,15 +711,38 @@ async fn main_inner() -> Result<(), ()> {
             Some((p.normalize().to_string_lossy().into_owned(), test_file))
         })
         .collect();
+    parsed_file_cache.save();
+
+    for test in all_tests.values() {
+        validate_refs(&test.file_directory, &test.steps, &all_tests, &mut errors);
+    }
+    for step_macro in all_macros.values() {
+        validate_refs(
+            &step_macro.file_directory,
+            &step_macro.steps,
+            &all_tests,
+            &mut errors,
+        );
+    }
 
     if !errors.is_empty() {
-        eprintln!("Toolproof failed to parse some files:");
+        log_eprintln!("Toolproof failed to parse some files:");
         for e in errors {
-            eprintln!("  • {e}");
+            log_eprintln!("  • {e}");
         }
         return Err(());
     }
 
+    if ctx.params.print_test_names {
+        for test in all_tests
+            .values()
+            .filter(|t| t.r#type == ToolproofFileType::Test)
+        {
+            println!("{}", test.name);
+        }
+        return Ok(());
+    }
+
     let macro_comparisons: Vec<_> = all_macros
         .keys()
         .map(|k| k.get_comparison_string())
@@ -392,13 +754,41 @@ async fn main_inner() -> Result<(), ()> {
         .map(|k| k.get_comparison_string())
         .collect();
 
-    let all_retrievers = register_retrievers();
+    let mut all_retrievers = register_retrievers();
+    let mut all_assertions = register_assertions();
+    if let Err(e) = register_custom_steps(custom_steps, &mut all_retrievers, &mut all_assertions) {
+        log_eprintln!("Toolproof failed to register custom steps:\n  • {e}");
+        return Err(());
+    }
+
+    if ctx.params.list_steps {
+        let mut steps = StepList::new(
+            &all_instructions,
+            &all_retrievers,
+            &all_assertions,
+            &all_macros,
+        );
+        if ctx.params.interactive {
+            let filter = ctx.params.list_steps_filter.as_deref();
+            pick_step(&Term::stdout(), &steps, filter).map_err(|e| log_eprintln!("{e}"))?;
+            return Ok(());
+        }
+        if let Some(filter) = &ctx.params.list_steps_filter {
+            steps = steps.filtered(filter);
+            if steps.is_empty() {
+                log_eprintln!("No steps contain \"{filter}\"");
+                return Err(());
+            }
+        }
+        steps.print();
+        return Ok(());
+    }
+
     let retriever_comparisons: Vec<_> = all_retrievers
         .keys()
         .map(|k| k.get_comparison_string())
         .collect();
 
-    let all_assertions = register_assertions();
     let assertion_comparisons: Vec<_> = all_assertions
         .keys()
         .map(|k| k.get_comparison_string())
@@ -420,7 +810,7 @@ async fn main_inner() -> Result<(), ()> {
 
     let run_mode = if let Some(run_name) = universe.ctx.params.run_name.as_ref() {
         let Some((path, _)) = universe.tests.iter().find(|(_, t)| t.name == *run_name) else {
-            eprintln!("Test name {run_name} does not exist");
+            log_eprintln!("Test name {run_name} does not exist");
             return Err(());
         };
 
@@ -438,7 +828,7 @@ async fn main_inner() -> Result<(), ()> {
 
         // Check if the path exists and is a file or directory
         if !absolute_path.exists() {
-            eprintln!("Path does not exist: {}", run_path.display());
+            log_eprintln!("Path does not exist: {}", run_path.display());
             return Err(());
         }
 
@@ -447,7 +837,7 @@ async fn main_inner() -> Result<(), ()> {
         match get_run_mode(&universe) {
             Ok(mode) => mode,
             Err(e) => {
-                eprintln!("{e}");
+                log_eprintln!("{e}");
                 return Err(());
             }
         }
@@ -457,7 +847,7 @@ async fn main_inner() -> Result<(), ()> {
 
     // Debugger mode requires running a single test
     if universe.ctx.params.debugger && !matches!(run_mode, RunMode::One(_)) {
-        eprintln!(
+        log_eprintln!(
             "Debugger mode requires running a single test. Please specify a test using --name."
         );
         return Err(());
@@ -491,7 +881,7 @@ async fn main_inner() -> Result<(), ()> {
             .count();
 
         if matching_tests == 0 {
-            eprintln!(
+            log_eprintln!(
                 "No tests found matching path: {}",
                 universe.ctx.params.run_path.as_ref().unwrap().display()
             );
@@ -519,7 +909,7 @@ async fn main_inner() -> Result<(), ()> {
         };
 
         let log_err_preamble = || {
-            println!(
+            log_println!(
                 "{}",
                 format!(
                     "{}{}{}",
@@ -528,39 +918,64 @@ async fn main_inner() -> Result<(), ()> {
                     &file.name.red().bold()
                 )
             );
-            println!("{}", style("--- STEPS ---").on_yellow().bold());
+            log_println!("{}", style("--- STEPS ---").on_yellow().bold());
             log_step_runs(&file.steps, 0);
         };
 
-        let output_doc = write_yaml_snapshots(&file.original_source, &file);
+        let output_doc = match write_yaml_snapshots(&file.original_source, &file) {
+            Ok(output_doc) => output_doc,
+            Err(e) => {
+                log_err_preamble();
+                log_println!("{}", "--- ERROR ---".on_yellow().bold());
+                log_println!("{}", &e.red());
+                return Err(HoldingError::TestFailure);
+            }
+        };
 
         match res {
             Ok(success) => {
                 match success {
                     ToolproofTestSuccess::Skipped => {
+                        let reason = file
+                            .skip
+                            .as_ref()
+                            .map(|reason| format!(" (skipped: {reason})"))
+                            .unwrap_or_default();
                         let msg = format!(
-                            "{}{}{}",
+                            "{}{}{}{}",
                             "⊝ ".green(),
                             dur.green().dimmed(),
-                            &file.name.green()
+                            &file.name.green(),
+                            reason
                         );
-                        println!("{}", style(msg).dim());
+                        if !universe.ctx.params.quiet {
+                            log_println!("{}", style(msg).dim());
+                        }
                         return Ok(success);
                     }
                     ToolproofTestSuccess::Passed { .. } => { /* continue to standard logging */ }
                 }
-                if output_doc.trim() == file.original_source.trim() {
+                // Both sides are normalized so that CRLF line endings on Windows
+                // don't show up as a changed snapshot
+                if normalize_line_endings(output_doc.trim())
+                    == normalize_line_endings(file.original_source.trim())
+                {
                     let msg = format!(
                         "{}{}{}",
                         "✓ ".green(),
                         dur.green().dimmed(),
                         &file.name.green()
                     );
-                    println!("{}", msg.green());
+                    if !universe.ctx.params.quiet {
+                        log_println!("{}", msg.green());
+                    }
                     Ok(success)
                 } else if universe.ctx.params.update {
-                    if let Err(e) = std::fs::write(&file.file_path, &output_doc) {
-                        eprintln!("Unable to write updated snapshot to disk.\n{e}");
+                    let on_disk = std::fs::read_to_string(&file.file_path).unwrap_or_default();
+                    if let Err(e) =
+                        std::fs::write(&file.file_path, match_line_endings(&output_doc, on_disk))
+                    {
+                        log_eprintln!("Unable to write updated snapshot to disk.\n{e}");
                         return Err(HoldingError::TestFailure);
                     }
                     let msg = format!(
@@ -570,10 +985,12 @@ async fn main_inner() -> Result<(), ()> {
                         &file.name.green(),
                         "(snapshot updated)".cyan()
                     );
-                    println!("{}", msg);
+                    if !universe.ctx.params.quiet {
+                        log_println!("{}", msg);
+                    }
                     Ok(ToolproofTestSuccess::Passed { attempts: 0 })
                 } else {
-                    println!(
+                    log_println!(
                         "{}",
                         format!(
                             "{}{}{}",
@@ -583,13 +1000,13 @@ async fn main_inner() -> Result<(), ()> {
                         )
                     );
                     if !universe.ctx.params.interactive {
-                        println!("{}\n", "--- SNAPSHOT CHANGED ---".on_bright_yellow().bold());
-                        println!("{}", diff_snapshots(&file.original_source, &output_doc));
-                        println!(
+                        log_println!("{}\n", "--- SNAPSHOT CHANGED ---".on_bright_yellow().bold());
+                        log_println!("{}", diff_snapshots(&file.original_source, &output_doc));
+                        log_println!(
                             "\n{}",
                             "--- END SNAPSHOT CHANGE ---".on_bright_yellow().bold()
                         );
-                        println!(
+                        log_println!(
                             "\n{}",
                             "Run in interactive mode (-i) or with --update (-u) to accept new snapshots\n"
                                 .bright_red()
@@ -602,8 +1019,8 @@ async fn main_inner() -> Result<(), ()> {
             Err(e) => {
                 let log_err = || {
                     log_err_preamble();
-                    println!("{}", "--- ERROR ---".on_yellow().bold());
-                    println!("{}", &e.red());
+                    log_println!("{}", "--- ERROR ---".on_yellow().bold());
+                    log_println!("{}", &e.red());
                 };
 
                 let log_closest = |step_type: &str,
@@ -614,24 +1031,15 @@ async fn main_inner() -> Result<(), ()> {
 
                     let matches = closest_strings(&comparator, comparisons);
 
-                    eprintln!(
+                    log_eprintln!(
                         "Unable to resolve: \"{}\"\n{step_type} \"{}\" was not found.",
                         original_segment_string.red(),
                         comparator.yellow(),
                     );
 
-                    matches
+                    suggested_strings(matches, &universe.ctx.params)
                         .into_iter()
-                        .enumerate()
-                        .filter_map(|(i, (s, score))| {
-                            if i > 5 && score < 0.6 {
-                                None
-                            } else if i > 0 && score < 0.4 {
-                                None
-                            } else {
-                                Some(parse_segments(&s).unwrap())
-                            }
-                        })
+                        .map(|s| parse_segments(s).unwrap())
                         .collect::<Vec<_>>()
                 };
 
@@ -639,9 +1047,9 @@ async fn main_inner() -> Result<(), ()> {
                     ToolproofStepError::External(ex) => match ex {
                         errors::ToolproofInputError::NonexistentStep => {
                             log_err_preamble();
-                            println!("{}", "--- ERROR ---".on_yellow().bold());
+                            log_println!("{}", "--- ERROR ---".on_yellow().bold());
                             match &e.step {
-                                ToolproofTestStep::Ref { .. } => println!("{}", &e.red()),
+                                ToolproofTestStep::Ref { .. } => log_println!("{}", &e.red()),
                                 ToolproofTestStep::Macro {
                                     step_macro, orig, ..
                                 } => {
@@ -667,9 +1075,9 @@ async fn main_inner() -> Result<(), ()> {
                                         .collect::<Vec<_>>();
 
                                     if matches.is_empty() {
-                                        eprintln!("{}", "No similar macro found".red());
+                                        log_eprintln!("{}", "No similar macro found".red());
                                     } else {
-                                        eprintln!("Closest macro:\n{}", matches.join("\n"));
+                                        log_eprintln!("Closest macro:\n{}", matches.join("\n"));
                                     }
                                 }
                                 ToolproofTestStep::Instruction { step, orig, .. } => {
@@ -695,14 +1103,17 @@ async fn main_inner() -> Result<(), ()> {
                                         .collect::<Vec<_>>();
 
                                     if matches.is_empty() {
-                                        eprintln!("{}", "No similar instructions found".red());
+                                        log_eprintln!("{}", "No similar instructions found".red());
                                     } else {
-                                        eprintln!("Closest instructions:\n{}", matches.join("\n"));
+                                        log_eprintln!(
+                                            "Closest instructions:\n{}",
+                                            matches.join("\n")
+                                        );
                                     }
                                 }
                                 ToolproofTestStep::Assertion {
                                     retrieval,
-                                    assertion,
+                                    assertions,
                                     orig,
                                     ..
                                 } => {
@@ -729,18 +1140,26 @@ async fn main_inner() -> Result<(), ()> {
                                             .collect::<Vec<_>>();
 
                                         if matches.is_empty() {
-                                            eprintln!("{}", "No similar retrievals found".red());
+                                            log_eprintln!(
+                                                "{}",
+                                                "No similar retrievals found".red()
+                                            );
                                         } else {
-                                            eprintln!(
+                                            log_eprintln!(
                                                 "Closest retrievals:\n{}",
                                                 matches.join("\n")
                                             );
                                         }
-                                    } else {
+                                    } else if let Some(assertion) = assertions.iter().find(|a| {
+                                        let (unwrapped, _) = a.unwrap_assertion(|a| {
+                                            universe.assertions.contains_key(a)
+                                        });
+                                        !universe.assertions.contains_key(&unwrapped)
+                                    }) {
                                         let closest = log_closest(
                                             "Assertion",
                                             &orig,
-                                            &assertion,
+                                            assertion,
                                             &universe.assertion_comparisons,
                                         );
 
@@ -759,9 +1178,12 @@ async fn main_inner() -> Result<(), ()> {
                                             .collect::<Vec<_>>();
 
                                         if matches.is_empty() {
-                                            eprintln!("{}", "No similar assertions found".red());
+                                            log_eprintln!(
+                                                "{}",
+                                                "No similar assertions found".red()
+                                            );
                                         } else {
-                                            eprintln!(
+                                            log_eprintln!(
                                                 "Closest assertions:\n{}",
                                                 matches.join("\n")
                                             );
@@ -782,14 +1204,23 @@ async fn main_inner() -> Result<(), ()> {
                 }
 
                 if let Some(failure_screenshot) = &file.failure_screenshot {
-                    println!("{}", "--- FAILURE SCREENSHOT ---".on_yellow().bold());
-                    println!(
+                    log_println!("{}", "--- FAILURE SCREENSHOT ---".on_yellow().bold());
+                    log_println!(
                         "{} {}",
                         "Browser state at failure was screenshot to".red(),
                         failure_screenshot.to_string_lossy().cyan().bold()
                     );
                 }
 
+                if let Some(kept_temp_dir) = &file.kept_temp_dir {
+                    log_println!("{}", "--- TEMP DIRECTORY ---".on_yellow().bold());
+                    log_println!(
+                        "{} {}",
+                        "Test directory at failure was kept at".red(),
+                        kept_temp_dir.to_string_lossy().cyan().bold()
+                    );
+                }
+
                 Err(HoldingError::TestFailure)
             }
         }
@@ -801,48 +1232,29 @@ async fn main_inner() -> Result<(), ()> {
         let _ = shutdown_tx.send(true);
     });
 
+    let deadline = RunDeadline::new(universe.ctx.params.max_run_duration);
+
     let semaphore = Arc::new(tokio::sync::Semaphore::new(universe.ctx.params.concurrency));
 
+    let progress = RunProgress::new(&universe.ctx.params);
+
     let mut hands = vec![];
     // Tracks the `universe.tests` key behind each spawned task,
     // in the same order as `hands`.
     let mut spawned_keys: Vec<String> = vec![];
 
-    println!("\n{}\n", "Running tests".bold());
+    log_println!("\n{}\n", "Running tests".bold());
 
-    match run_mode {
-        RunMode::All => {
-            for (key, mut test) in universe
-                .tests
-                .iter()
-                .filter(|(_, v)| v.r#type == ToolproofFileType::Test)
-                .map(|(k, v)| (k.clone(), v.clone()))
-            {
-                let permit = acquire_or_shutdown(&semaphore, &shutdown_rx, &hands).await?;
-                let uni = Arc::clone(&universe);
-                spawned_keys.push(key);
-                hands.push(tokio::spawn(async move {
-                    let start = Instant::now();
-                    let res = run_toolproof_experiment(&mut test, Arc::clone(&uni)).await;
-                    let holding_err = handle_res(uni, (&test, res), start);
-
-                    drop(permit);
-
-                    holding_err.map_err(|e| (test, e))
-                }));
-            }
-        }
+    let selected_tests: Vec<(String, ToolproofTestFile)> = match run_mode {
+        RunMode::All => universe
+            .tests
+            .iter()
+            .filter(|(_, v)| v.r#type == ToolproofFileType::Test)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
         RunMode::One(t) => {
-            let mut test = universe.tests.get(&t).cloned().unwrap();
-            let uni = Arc::clone(&universe);
-            spawned_keys.push(t.clone());
-            hands.push(tokio::spawn(async move {
-                let start = Instant::now();
-                let res = run_toolproof_experiment(&mut test, Arc::clone(&uni)).await;
-                let holding_err = handle_res(uni, (&test, res), start);
-
-                holding_err.map_err(|e| (test, e))
-            }));
+            let test = universe.tests.get(&t).cloned().unwrap();
+            vec![(t, test)]
         }
         RunMode::Path(ref filter_path) => {
             let test_root = universe
@@ -853,7 +1265,7 @@ async fn main_inner() -> Result<(), ()> {
                 .cloned()
                 .unwrap_or_else(|| universe.ctx.working_directory.clone());
 
-            for (key, mut test) in universe
+            universe
                 .tests
                 .iter()
                 .filter(|(test_path, v)| {
@@ -869,35 +1281,53 @@ async fn main_inner() -> Result<(), ()> {
                         || absolute_test_path_str.starts_with(filter_path.as_str())
                 })
                 .map(|(k, v)| (k.clone(), v.clone()))
-            {
-                let permit = acquire_or_shutdown(&semaphore, &shutdown_rx, &hands).await?;
-                let uni = Arc::clone(&universe);
-                spawned_keys.push(key);
-                hands.push(tokio::spawn(async move {
-                    let start = Instant::now();
-                    let res = run_toolproof_experiment(&mut test, Arc::clone(&uni)).await;
-                    let holding_err = handle_res(uni, (&test, res), start);
+                .collect()
+        }
+    };
 
-                    drop(permit);
+    // Each round runs every selected test once, so that repeats of a test are spread out
+    let repeat = universe.ctx.params.repeat.max(1);
+    for _ in 0..repeat {
+        for (key, test) in &selected_tests {
+            let permit = acquire_or_shutdown(&semaphore, &shutdown_rx, &deadline, &hands).await?;
+            let uni = Arc::clone(&universe);
+            let progress = progress.clone();
+            let mut test = test.clone();
+            progress.add_test();
+            spawned_keys.push(key.clone());
+            hands.push(tokio::spawn(async move {
+                let start = Instant::now();
+                let res = run_toolproof_experiment(&mut test, Arc::clone(&uni)).await;
+                let elapsed = start.elapsed();
+                let holding_err = progress.complete(|| handle_res(uni, (&test, res), start));
 
-                    holding_err.map_err(|e| (test, e))
-                }));
-            }
+                drop(permit);
+
+                (elapsed, holding_err.map_err(|e| (test, e)))
+            }));
         }
     }
 
-    let mut results = join_or_shutdown(hands, &shutdown_rx)
-        .await?
+    let joined = join_or_shutdown(hands, &shutdown_rx, &deadline).await;
+    progress.finish();
+
+    // How long each test took to run, in the same order as `results`
+    let mut durations: Vec<Option<Duration>> = vec![];
+    let mut results = joined?
         .into_iter()
-        .zip(spawned_keys)
+        .zip(spawned_keys.iter())
         .map(|(outer_err, key)| match outer_err {
-            Ok(inner) => inner,
+            Ok((elapsed, inner)) => {
+                durations.push(Some(elapsed));
+                inner
+            }
             Err(e) => {
-                eprintln!("[toolproof] Error: A test task panicked: {e}");
+                durations.push(None);
+                log_eprintln!("[toolproof] Error: A test task panicked: {e}");
                 // Count the panic as a failure (and let it be retried)
                 let test = universe
                     .tests
-                    .get(&key)
+                    .get(key)
                     .cloned()
                     .expect("spawned key must exist in universe.tests");
                 Err((test, HoldingError::TestFailure))
@@ -905,16 +1335,26 @@ async fn main_inner() -> Result<(), ()> {
         })
         .collect::<Vec<_>>();
 
-    let retry_count = universe.ctx.params.retry_count;
+    // Retrying would hide the flaky tests that repeating tests is looking for
+    let retry_count = if repeat > 1 {
+        0
+    } else {
+        universe.ctx.params.retry_count
+    };
     let mut concurrency = universe.ctx.params.concurrency;
+    // Quarantined tests don't fail the run, so aren't worth the time to retry
+    let needs_retry = |r: &Result<_, (ToolproofTestFile, HoldingError)>| match r {
+        Err((test, _)) => !test.quarantine,
+        Ok(_) => false,
+    };
     for i in 0..retry_count {
-        if !results.iter().any(|r| r.is_err()) {
+        if !results.iter().any(needs_retry) {
             break;
         }
 
         let remaining_attempts = retry_count - i;
         concurrency = concurrency.div(2).max(1);
-        println!(
+        log_println!(
             "{}",
             style(&format!(
                 "\nSome tests failed. Retrying {} at concurrency {concurrency}.",
@@ -930,20 +1370,23 @@ async fn main_inner() -> Result<(), ()> {
         let semaphore = Arc::new(tokio::sync::Semaphore::new(concurrency));
         let mut hands = vec![];
 
-        for (result_index, result) in results.iter().enumerate().filter(|(_, r)| r.is_err()) {
+        for (result_index, result) in results.iter().enumerate().filter(|(_, r)| needs_retry(r)) {
             if let Err((test, _)) = result {
-                let permit = acquire_or_shutdown(&semaphore, &shutdown_rx, &hands).await?;
+                let permit =
+                    acquire_or_shutdown(&semaphore, &shutdown_rx, &deadline, &hands).await?;
                 let uni = Arc::clone(&universe);
                 let mut new_test = test.clone();
                 hands.push(tokio::spawn(async move {
                     let start = Instant::now();
                     let res = run_toolproof_experiment(&mut new_test, Arc::clone(&uni)).await;
+                    let elapsed = start.elapsed();
                     let holding_err = handle_res(uni, (&new_test, res), start);
 
                     drop(permit);
 
                     (
                         result_index,
+                        elapsed,
                         holding_err.map_err(|e| (new_test, e)).map(|r| {
                             if matches!(r, ToolproofTestSuccess::Passed { .. }) {
                                 ToolproofTestSuccess::Passed { attempts: i + 1 }
@@ -956,15 +1399,17 @@ async fn main_inner() -> Result<(), ()> {
             }
         }
 
-        for (result_index, retried_result) in join_or_shutdown(hands, &shutdown_rx)
-            .await?
-            .into_iter()
-            .filter_map(|outer_err| match outer_err {
-                Ok((i, Ok(success))) => Some((i, success)),
-                _ => None,
-            })
+        for (result_index, elapsed, retried_result) in
+            join_or_shutdown(hands, &shutdown_rx, &deadline)
+                .await?
+                .into_iter()
+                .filter_map(Result::ok)
         {
-            results[result_index] = Ok(retried_result);
+            // A retried test's time covers all of its attempts
+            *durations[result_index].get_or_insert(Duration::ZERO) += elapsed;
+            if let Ok(success) = retried_result {
+                results[result_index] = Ok(success);
+            }
         }
     }
 
@@ -977,7 +1422,7 @@ async fn main_inner() -> Result<(), ()> {
         .collect::<Vec<_>>();
     let mut resolved: Vec<String> = vec![];
 
-    println!("\n{}\n", "Finished running tests".bold());
+    log_println!("\n{}\n", "Finished running tests".bold());
 
     let interactive = universe.ctx.params.interactive;
     if interactive && !snapshot_failures.is_empty() {
@@ -992,7 +1437,7 @@ async fn main_inner() -> Result<(), ()> {
         )) {
             Ok(b) => b,
             Err(e) => {
-                eprintln!("{e}");
+                log_eprintln!("{e}");
                 return Err(());
             }
         };
@@ -1010,15 +1455,72 @@ async fn main_inner() -> Result<(), ()> {
                         if confirm_snapshot(&term, &file, &out).is_ok_and(|v| v) {
                             resolved.push(file.file_path.clone());
 
-                            if let Err(e) = tokio::fs::write(&file.file_path, out).await {
-                                eprintln!("Unable to write updates snapshot to disk.\n{e}");
+                            let on_disk = tokio::fs::read_to_string(&file.file_path)
+                                .await
+                                .unwrap_or_default();
+                            if let Err(e) =
+                                tokio::fs::write(&file.file_path, match_line_endings(out, on_disk))
+                                    .await
+                            {
+                                log_eprintln!("Unable to write updates snapshot to disk.\n{e}");
                                 return Err(());
                             }
                         }
                     }
                 }
             }
-            println!("\n\n");
+            log_println!("\n\n");
+        }
+    }
+
+    let changed_images = results
+        .iter()
+        .filter_map(|r| match r {
+            Err((file, HoldingError::TestFailure)) => Some(&file.changed_images),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    if interactive && !changed_images.is_empty() {
+        let review_images = match question(format!(
+            "{} {}. Review now?",
+            changed_images.len(),
+            if changed_images.len() == 1 {
+                "screenshot doesn't match its baseline"
+            } else {
+                "screenshots don't match their baselines"
+            },
+        )) {
+            Ok(b) => b,
+            Err(e) => {
+                log_eprintln!("{e}");
+                return Err(());
+            }
+        };
+
+        if review_images {
+            for (baseline, actual) in changed_images {
+                let accept = match question(format!(
+                    "Accept {} as the new {}?",
+                    actual.to_string_lossy().magenta(),
+                    baseline.to_string_lossy().cyan()
+                )) {
+                    Ok(b) => b,
+                    Err(e) => {
+                        log_eprintln!("{e}");
+                        return Err(());
+                    }
+                };
+
+                if accept {
+                    if let Err(e) = tokio::fs::rename(actual, baseline).await {
+                        log_eprintln!("Unable to write new baseline image to disk.\n{e}");
+                        return Err(());
+                    }
+                    _ = tokio::fs::remove_file(baseline.with_extension("diff.png")).await;
+                }
+            }
+            log_println!("\n\n");
         }
     }
 
@@ -1033,10 +1535,23 @@ async fn main_inner() -> Result<(), ()> {
         )
     };
 
+    // Quarantined tests are reported separately however they failed
+    let quarantined_failures = results
+        .iter()
+        .filter_map(|r| match r {
+            Err((file, HoldingError::TestFailure)) if file.quarantine => Some(file),
+            Err((file, HoldingError::SnapFailure { .. }))
+                if file.quarantine && !resolved.contains(&file.file_path) =>
+            {
+                Some(file)
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
     let hard_failures = results
         .iter()
         .filter_map(|r| match r {
-            Err((file, HoldingError::TestFailure)) => Some(file),
+            Err((file, HoldingError::TestFailure)) if !file.quarantine => Some(file),
             _ => None,
         })
         .collect::<Vec<_>>();
@@ -1044,7 +1559,7 @@ async fn main_inner() -> Result<(), ()> {
         .iter()
         .filter_map(|r| match r {
             Err((file, HoldingError::SnapFailure { .. }))
-                if !resolved.contains(&file.file_path) =>
+                if !file.quarantine && !resolved.contains(&file.file_path) =>
             {
                 Some(file)
             }
@@ -1063,7 +1578,7 @@ async fn main_inner() -> Result<(), ()> {
         .filter(|r| matches!(r, Ok(ToolproofTestSuccess::Skipped)))
         .count();
 
-    let retried_passed = if universe.ctx.params.retry_count > 0 {
+    let retried_passed = if retry_count > 0 {
         results
             .iter()
             .filter(|r| matches!(r, Ok(ToolproofTestSuccess::Passed { attempts: run }) if *run > 0))
@@ -1072,7 +1587,7 @@ async fn main_inner() -> Result<(), ()> {
         0
     };
 
-    println!(
+    log_println!(
         "{}\n{}\n{}\n{}\n{}",
         style(&format!("Total passing tests: {}", passing)).cyan(),
         style(&format!("Passed after retry: {}", retried_passed)).cyan(),
@@ -1083,40 +1598,142 @@ async fn main_inner() -> Result<(), ()> {
 
     let shell_quote = |s: &str| format!("'{}'", s.replace('\'', "'\\''"));
 
-    if !hard_failures.is_empty() {
-        println!("\n{}", "Failing tests:".red().bold());
-        for file in &hard_failures {
-            println!("  {} {}", "✘".red().bold(), file.name.red());
+    let skipped_with_reason = results
+        .iter()
+        .zip(spawned_keys.iter())
+        .filter_map(|(r, key)| match r {
+            Ok(ToolproofTestSuccess::Skipped) => universe
+                .tests
+                .get(key)
+                .and_then(|file| file.skip.as_ref().map(|reason| (&file.name, reason))),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let slowest = universe.ctx.params.slowest;
+    if slowest > 0 {
+        let mut timed_tests = results
+            .iter()
+            .zip(spawned_keys.iter())
+            .zip(durations.iter())
+            .filter_map(|((r, key), elapsed)| match (r, elapsed) {
+                (Ok(ToolproofTestSuccess::Skipped), _) | (_, None) => None,
+                (_, Some(elapsed)) => universe.tests.get(key).map(|file| (&file.name, elapsed)),
+            })
+            .collect::<Vec<_>>();
+        timed_tests.sort_by(|a, b| b.1.cmp(a.1));
+
+        log_println!("\n{}", "Slowest tests:".bold());
+        for (name, elapsed) in timed_tests.into_iter().take(slowest) {
+            // Timings change between runs, so are left out of stable output
+            if universe.ctx.params.porcelain {
+                log_println!("  {name}");
+            } else {
+                let elapsed = format!("{}.{:03}s", elapsed.as_secs(), elapsed.subsec_millis());
+                log_println!("  {} {name}", elapsed.yellow());
+            }
+        }
+    }
+
+    if !skipped_with_reason.is_empty() {
+        log_println!("\n{}", "Skipped tests:".bold());
+        for (name, reason) in &skipped_with_reason {
+            log_println!("  {} {}: {}", "⊝".dimmed(), name, reason.dimmed());
+        }
+    }
+
+    // Repeated tests can fail more than once, but are only listed once
+    let mut listed = HashSet::new();
+    let failing_names = hard_failures
+        .iter()
+        .map(|file| &file.name)
+        .filter(|name| listed.insert(*name))
+        .collect::<Vec<_>>();
+
+    if !failing_names.is_empty() {
+        log_println!("\n{}", "Failing tests:".red().bold());
+        for name in &failing_names {
+            log_println!("  {} {}", "✘".red().bold(), name.red());
         }
-        println!("\n{}", "Rerun the failing tests with:".bold());
-        for file in &hard_failures {
-            println!("  toolproof --name {}", shell_quote(&file.name));
+        log_println!("\n{}", "Rerun the failing tests with:".bold());
+        for name in &failing_names {
+            log_println!("  toolproof --name {}", shell_quote(name));
+        }
+    }
+
+    let mut listed = HashSet::new();
+    let quarantined_names = quarantined_failures
+        .iter()
+        .map(|file| &file.name)
+        .filter(|name| listed.insert(*name))
+        .collect::<Vec<_>>();
+
+    if !quarantined_names.is_empty() {
+        log_println!(
+            "\n{}",
+            "Failing quarantined tests (not counted as failures):"
+                .yellow()
+                .bold()
+        );
+        for name in &quarantined_names {
+            log_println!("  {} {}", "✘".yellow().bold(), name.yellow());
         }
     }
 
+    let mut listed = HashSet::new();
+    let changed_snapshots = changed_snapshots
+        .into_iter()
+        .filter(|file| listed.insert(&file.name))
+        .collect::<Vec<_>>();
+
     if !changed_snapshots.is_empty() {
-        println!("\n{}", "Changed snapshots:".yellow().bold());
+        log_println!("\n{}", "Changed snapshots:".yellow().bold());
         for file in &changed_snapshots {
-            println!("  {} {}", "⚠".yellow().bold(), file.name.yellow());
+            log_println!("  {} {}", "⚠".yellow().bold(), file.name.yellow());
         }
-        println!(
+        log_println!(
             "\n{}",
             "Review the changed snapshots. If a change is correct, accept it with --update (-u):"
                 .bold()
         );
         for file in &changed_snapshots {
-            println!("  toolproof --name {} --update", shell_quote(&file.name));
+            log_println!("  toolproof --name {} --update", shell_quote(&file.name));
+        }
+    }
+
+    if repeat > 1 {
+        log_println!("\n{}", format!("Pass rates over {repeat} runs:").bold());
+        for (key, test) in &selected_tests {
+            let (runs, passes) = results
+                .iter()
+                .zip(spawned_keys.iter())
+                .filter(|(_, k)| *k == key)
+                .fold((0, 0), |(runs, passes), (r, _)| match r {
+                    Ok(ToolproofTestSuccess::Skipped) => (runs, passes),
+                    Ok(ToolproofTestSuccess::Passed { .. }) => (runs + 1, passes + 1),
+                    Err(_) => (runs + 1, passes),
+                });
+            if runs == 0 {
+                continue;
+            }
+
+            let rate = format!("{passes}/{runs} ({}%)", passes * 100 / runs);
+            if passes == runs {
+                log_println!("  {} {} {}", "✓".green().bold(), rate.green(), test.name);
+            } else {
+                log_println!("  {} {} {}", "✘".red().bold(), rate.red(), test.name);
+            }
         }
     }
 
     if failing > 0 {
-        println!(
+        log_println!(
             "{}",
             style(&format!("\nSome tests failed{}", duration)).red()
         );
         return Err(());
     } else {
-        println!(
+        log_println!(
             "{}",
             style(&format!("\nAll tests passed{}", duration)).green()
         );
@@ -1132,3 +1749,51 @@ async fn main() -> std::process::ExitCode {
         Err(_) => std::process::ExitCode::FAILURE,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_suggesting_a_limited_number_of_strings() {
+        let options = (0..10).map(|i| format!("step {i}")).collect::<Vec<_>>();
+        let params = ToolproofParams {
+            suggestion_threshold: 0.4,
+            suggestion_limit: 5,
+            suggestion_close_match: 0.6,
+            ..Default::default()
+        };
+
+        let matches = options.iter().map(|s| (s, 0.5)).collect::<Vec<_>>();
+        assert_eq!(suggested_strings(matches, &params).len(), 5);
+
+        let matches = options.iter().map(|s| (s, 0.7)).collect::<Vec<_>>();
+        assert_eq!(suggested_strings(matches, &params).len(), 10);
+
+        // The closest string is suggested even if nothing scores above the threshold
+        let matches = options.iter().map(|s| (s, 0.1)).collect::<Vec<_>>();
+        assert_eq!(suggested_strings(matches, &params), vec![&options[0]]);
+    }
+
+    #[test]
+    fn test_closest_strings_are_ordered_by_similarity() {
+        let options = vec![
+            "I run {command}".to_string(),
+            "zzz".to_string(),
+            "I run {command} and expect it to fail".to_string(),
+        ];
+
+        let closest = closest_strings("I run {command}", &options)
+            .into_iter()
+            .map(|(s, _)| s.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            closest,
+            vec![
+                "I run {command}",
+                "I run {command} and expect it to fail",
+                "zzz"
+            ]
+        );
+    }
+}
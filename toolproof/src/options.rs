@@ -1,4 +1,5 @@
-use clap::{arg, builder::PossibleValuesParser, command, value_parser, ArgMatches};
+use clap::{arg, builder::PossibleValuesParser, command, value_parser, ArgMatches, Command};
+use clap_complete::Shell;
 use miette::IntoDiagnostic;
 use schematic::{Config, ConfigEnum, ConfigLoader};
 use serde::{Deserialize, Serialize};
@@ -14,24 +15,45 @@ const CONFIGS: &[&str] = &[
 pub fn configure() -> ToolproofContext {
     let cli_matches = get_cli_matches();
 
-    let configs: Vec<&str> = CONFIGS
-        .iter()
-        .filter(|c| std::path::Path::new(c).exists())
-        .cloned()
-        .collect();
+    if let Some(shell) = cli_matches.get_one::<Shell>("print-completions") {
+        clap_complete::generate(
+            *shell,
+            &mut cli_command(),
+            "toolproof",
+            &mut std::io::stdout(),
+        );
+        std::process::exit(0);
+    }
+
+    let configs: Vec<PathBuf> = if let Some(config) = cli_matches.get_one::<PathBuf>("config") {
+        // An explicit config file skips discovery, so it can be used
+        // alongside other config files in the same directory
+        if !config.exists() {
+            eprintln!("Config file does not exist: {}", config.display());
+            std::process::exit(1);
+        }
+        vec![config.clone()]
+    } else {
+        CONFIGS
+            .iter()
+            .map(PathBuf::from)
+            .filter(|c| c.exists())
+            .collect()
+    };
     if configs.len() > 1 {
+        let configs: Vec<_> = configs.iter().map(|c| c.to_string_lossy()).collect();
         eprintln!(
             "Found multiple possible config files: [{}]",
             configs.join(", ")
         );
-        eprintln!("Toolproof only supports loading one configuration file format, please ensure only one file exists.");
+        eprintln!("Toolproof only supports loading one configuration file format, please ensure only one file exists, or choose one with --config.");
         std::process::exit(1);
     }
 
     let mut loader = ConfigLoader::<ToolproofParams>::new();
     for config in configs {
-        if let Err(e) = loader.file(config).into_diagnostic() {
-            eprintln!("Failed to load {config}:\n{e:?}");
+        if let Err(e) = loader.file(config.as_path()).into_diagnostic() {
+            eprintln!("Failed to load {}:\n{e:?}", config.display());
             std::process::exit(1);
         }
     }
@@ -56,6 +78,10 @@ pub fn configure() -> ToolproofContext {
 }
 
 fn get_cli_matches() -> ArgMatches {
+    cli_command().get_matches()
+}
+
+fn cli_command() -> Command {
     command!()
         .arg(
             arg!(
@@ -64,6 +90,13 @@ fn get_cli_matches() -> ArgMatches {
             .required(false)
             .value_parser(value_parser!(PathBuf)),
         )
+        .arg(
+            arg!(
+                --config <FILE> "The configuration file to load, instead of discovering one"
+            )
+            .required(false)
+            .value_parser(value_parser!(PathBuf)),
+        )
         .arg(
             arg!(
                 -c --concurrency <NUM> "How many tests should be run concurrently"
@@ -73,7 +106,7 @@ fn get_cli_matches() -> ArgMatches {
         )
         .arg(
             arg!(--placeholders <PAIRS> "Define placeholders for tests")
-                .long_help("e.g. --placeholders key=value second_key=second_value")
+                .long_help("e.g. --placeholders key=value second_key=second_value\nValues starting with @ are read from a file, e.g. --placeholders token=@secrets/token.txt\nStart a value with @@ for a literal value starting with @, e.g. --placeholders handle=@@user")
                 .required(false)
                 .num_args(0..),
         )
@@ -93,6 +126,19 @@ fn get_cli_matches() -> ArgMatches {
             )
             .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            arg!(
+                --"no-color" ... "Print output without colors or styling"
+            )
+            .long_help("Also enabled by setting the NO_COLOR environment variable")
+            .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                -q --quiet ... "Only print failing tests and the final summary"
+            )
+            .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             arg!(
                 -i --interactive ... "Run toolproof in interactive mode"
@@ -117,6 +163,12 @@ fn get_cli_matches() -> ArgMatches {
             )
             .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            arg!(
+                --"no-parse-cache" ... "Parse every test file, without reading or writing .toolproof/parse_cache.json"
+            )
+            .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             arg!(
                 --timeout <NUM> "How long in seconds until a step times out"
@@ -131,6 +183,18 @@ fn get_cli_matches() -> ArgMatches {
             .required(false)
             .value_parser(value_parser!(u64)),
         )
+        .arg(
+            arg!(
+                --"user-agent" <UA> "The user agent that browsers should send, instead of the browser's own"
+            )
+            .required(false),
+        )
+        .arg(
+            arg!(
+                --"reuse-browser-context" ... "Reuse browser contexts between tests, which is faster but lets cookies and storage leak between tests"
+            )
+            .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             arg!(
                 -n --name <NAME> "Exact name of a test to run")
@@ -158,6 +222,28 @@ fn get_cli_matches() -> ArgMatches {
             .required(false)
             .value_parser(value_parser!(usize)),
         )
+        .arg(
+            arg!(
+                --repeat <N> "Run each selected test this many times, and report how often each test passed"
+            )
+            .long_help("For finding flaky tests. Failed tests aren't retried when repeating tests")
+            .required(false)
+            .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(
+                --slowest <N> "Print the N slowest tests at the end of the run"
+            )
+            .required(false)
+            .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(
+                --"retry-browser-steps" <COUNT> "Number of times to retry failed browser steps before failing the test"
+            )
+            .required(false)
+            .value_parser(value_parser!(usize)),
+        )
         .arg(
             arg!(
                 --"failure-screenshot-location" <DIR> "If set, Toolproof will screenshot the browser to this location when a test fails (if applicable)"
@@ -171,7 +257,55 @@ fn get_cli_matches() -> ArgMatches {
             )
             .action(clap::ArgAction::SetTrue),
         )
-        .get_matches()
+        .arg(
+            arg!(
+                --"max-run-duration" <NUM> "How long in seconds the whole run can take before outstanding tests are cancelled"
+            )
+            .required(false)
+            .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            arg!(
+                --"keep-temp-on-failure" ... "Keep the temporary directory of a failing test for inspection"
+            )
+            .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(
+                --"log-file" <PATH> "Also write the test output to this file, without colors"
+            )
+            .required(false)
+            .value_parser(value_parser!(PathBuf)),
+        )
+        .arg(
+            arg!(
+                --"print-completions" <SHELL> "Print a shell completion script and exit"
+            )
+            .required(false)
+            .hide(true)
+            .value_parser(value_parser!(Shell)),
+        )
+        .arg(
+            arg!(
+                --"print-test-names" ... "Print the names of all discovered tests and exit"
+            )
+            .long_help("Intended for dynamic shell completion of --name")
+            .action(clap::ArgAction::SetTrue),
+        )
+        .subcommand(
+            Command::new("new")
+                .about("Create a new test file in the root directory, named after the test")
+                .arg(arg!(<NAME> "The name of the new test")),
+        )
+        .subcommand(
+            Command::new("steps")
+                .about("List every instruction, retrieval, assertion, and macro that can be used in a test")
+                .arg(arg!([FILTER] "Only list steps containing this text"))
+                .arg(
+                    arg!(-i --interactive "Search the steps as you type, then print or copy one")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
 }
 
 #[derive(ConfigEnum, Default, Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
@@ -195,6 +329,23 @@ pub struct ToolproofParams {
     #[setting(env = "TOOLPROOF_ROOT")]
     pub root: Option<PathBuf>,
 
+    /// Additional locations to look for toolproof test files, alongside `root`
+    pub roots: Vec<PathBuf>,
+
+    /// The glob, relative to each root, that finds test files
+    #[setting(env = "TOOLPROOF_TEST_GLOB")]
+    #[setting(default = "**/*.toolproof.yml")]
+    pub test_glob: String,
+
+    /// The glob, relative to each root, that finds macro files
+    #[setting(env = "TOOLPROOF_MACRO_GLOB")]
+    #[setting(default = "**/*.toolproof.macro.yml")]
+    pub macro_glob: String,
+
+    /// Globs of paths to skip when looking for test and macro files
+    #[setting(default = default_ignore)]
+    pub ignore: Vec<String>,
+
     /// Print verbose logging while building. Does not impact the output files
     #[setting(env = "TOOLPROOF_VERBOSE")]
     pub verbose: bool,
@@ -203,12 +354,32 @@ pub struct ToolproofParams {
     #[setting(env = "TOOLPROOF_PORCELAIN")]
     pub porcelain: bool,
 
+    /// Only print failing tests and the final summary
+    #[setting(env = "TOOLPROOF_QUIET")]
+    pub quiet: bool,
+
+    /// Print output without colors or styling
+    #[setting(env = "TOOLPROOF_NO_COLOR")]
+    pub no_color: bool,
+
     /// Run toolproof in interactive mode
     pub interactive: bool,
 
     /// Run all tests when in interactive mode
     pub all: bool,
 
+    /// Print the names of all discovered tests and exit
+    pub print_test_names: bool,
+
+    /// Create a new test file with this name and exit
+    pub new_test: Option<String>,
+
+    /// List the available steps and exit
+    pub list_steps: bool,
+
+    /// Only list the available steps containing this text
+    pub list_steps_filter: Option<String>,
+
     /// Automatically accept all snapshot changes
     #[setting(env = "TOOLPROOF_UPDATE")]
     pub update: bool,
@@ -240,6 +411,14 @@ pub struct ToolproofParams {
     #[setting(default = 8)]
     pub browser_timeout: u64,
 
+    /// The user agent that browsers should send, instead of the browser's own
+    #[setting(env = "TOOLPROOF_USER_AGENT")]
+    pub user_agent: Option<String>,
+
+    /// Reuse browser contexts between tests, which is faster but lets cookies and storage leak between tests
+    #[setting(env = "TOOLPROOF_REUSE_BROWSER_CONTEXT")]
+    pub reuse_browser_context: bool,
+
     /// What delimiter should be used when replacing placeholders
     #[setting(env = "TOOLPROOF_PLACEHOLDER_DELIM")]
     #[setting(default = "%")]
@@ -251,10 +430,22 @@ pub struct ToolproofParams {
     /// Commands to run in the working directory before starting to run Toolproof tests
     pub before_all: Vec<ToolproofBeforeAll>,
 
+    /// Environment variables set for the commands run by every test,
+    /// alongside any that `before_all` commands write to `$TOOLPROOF_ENV`
+    pub env: HashMap<String, String>,
+
     /// Skip running any of the before_all hooks
     #[setting(env = "TOOLPROOF_SKIPHOOKS")]
     pub skip_hooks: bool,
 
+    /// Parse every test file, without using the cache of parsed files
+    #[setting(env = "TOOLPROOF_NO_PARSE_CACHE")]
+    pub no_parse_cache: bool,
+
+    /// A directory of `.toolproof.step.yml` files defining custom retrievers and assertions
+    #[setting(env = "TOOLPROOF_CUSTOM_STEPS")]
+    pub custom_steps: Option<PathBuf>,
+
     /// Error if Toolproof is below this version
     #[setting(env = "TOOLPROOF_SUPPORTED_VERSIONS")]
     pub supported_versions: Option<String>,
@@ -263,14 +454,75 @@ pub struct ToolproofParams {
     #[setting(env = "TOOLPROOF_FAILURE_SCREENSHOT_LOCATION")]
     pub failure_screenshot_location: Option<PathBuf>,
 
+    /// How long in seconds the whole run can take before outstanding tests are cancelled
+    #[setting(env = "TOOLPROOF_MAX_RUN_DURATION")]
+    pub max_run_duration: Option<u64>,
+
+    /// Keep the temporary directory of a failing test, rather than deleting it
+    #[setting(env = "TOOLPROOF_KEEP_TEMP_ON_FAILURE")]
+    pub keep_temp_on_failure: bool,
+
+    /// Also write the test output to this file, without colors
+    #[setting(env = "TOOLPROOF_LOG_FILE")]
+    pub log_file: Option<PathBuf>,
+
     /// Number of times to retry failed tests before marking them as failed
     #[setting(env = "TOOLPROOF_RETRY_COUNT")]
     #[setting(default = 0)]
     pub retry_count: usize,
 
+    /// Number of times to run each selected test, to find flaky tests
+    #[setting(env = "TOOLPROOF_REPEAT")]
+    #[setting(default = 1)]
+    pub repeat: usize,
+
+    /// How many of the slowest tests to print at the end of the run
+    #[setting(env = "TOOLPROOF_SLOWEST")]
+    #[setting(default = 0)]
+    pub slowest: usize,
+
+    /// Number of times to retry failed browser steps before failing the test
+    #[setting(env = "TOOLPROOF_RETRY_BROWSER_STEPS")]
+    #[setting(default = 0)]
+    pub retry_browser_steps: usize,
+
     /// Run in debugger mode with step-by-step execution
     #[setting(env = "TOOLPROOF_DEBUGGER")]
     pub debugger: bool,
+
+    /// Minimum similarity score (0 to 1) for a step to be suggested when a step can't be found
+    #[setting(env = "TOOLPROOF_SUGGESTION_THRESHOLD")]
+    #[setting(default = 0.4)]
+    pub suggestion_threshold: f64,
+
+    /// How many suggestions to show before only showing very close matches
+    #[setting(env = "TOOLPROOF_SUGGESTION_LIMIT")]
+    #[setting(default = 5)]
+    pub suggestion_limit: usize,
+
+    /// Minimum similarity score (0 to 1) for a step to be suggested beyond the `suggestion_limit`
+    #[setting(env = "TOOLPROOF_SUGGESTION_CLOSE_MATCH")]
+    #[setting(default = 0.6)]
+    pub suggestion_close_match: f64,
+
+    /// How deeply references and macros can include each other before a test fails
+    #[setting(env = "TOOLPROOF_MAX_NESTING_DEPTH")]
+    #[setting(default = 50)]
+    pub max_nesting_depth: usize,
+
+    /// The fraction of pixels (0 to 1) that can differ before a screenshot doesn't match its baseline image
+    #[setting(env = "TOOLPROOF_IMAGE_DIFF_TOLERANCE")]
+    #[setting(default = 0.0)]
+    pub image_diff_tolerance: f64,
+}
+
+fn default_ignore(_: &()) -> schematic::DefaultValueResult<Vec<String>> {
+    Ok(Some(
+        ["**/node_modules/**", "**/target/**", "**/.git/**"]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    ))
 }
 
 // The configuration object used internally
@@ -289,6 +541,19 @@ impl ToolproofContext {
             *root = working_directory.join(root.clone());
         }
 
+        for root in config.roots.iter_mut() {
+            *root = working_directory.join(root.clone());
+        }
+
+        if let Some(custom_steps) = config.custom_steps.as_mut() {
+            *custom_steps = working_directory.join(custom_steps.clone());
+        }
+
+        // https://no-color.org: any non-empty value disables color
+        if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            config.no_color = true;
+        }
+
         Ok(Self {
             working_directory,
             version: env!("CARGO_PKG_VERSION"),
@@ -307,6 +572,14 @@ impl ToolproofParams {
             self.porcelain = true;
         }
 
+        if cli_matches.get_flag("quiet") {
+            self.quiet = true;
+        }
+
+        if cli_matches.get_flag("no-color") {
+            self.no_color = true;
+        }
+
         if cli_matches.get_flag("interactive") {
             self.interactive = true;
         }
@@ -315,6 +588,22 @@ impl ToolproofParams {
             self.all = true;
         }
 
+        if cli_matches.get_flag("print-test-names") {
+            self.print_test_names = true;
+        }
+
+        if let Some(new_matches) = cli_matches.subcommand_matches("new") {
+            self.new_test = new_matches.get_one::<String>("NAME").cloned();
+        }
+
+        if let Some(steps_matches) = cli_matches.subcommand_matches("steps") {
+            self.list_steps = true;
+            self.list_steps_filter = steps_matches.get_one::<String>("FILTER").cloned();
+            if steps_matches.get_flag("interactive") {
+                self.interactive = true;
+            }
+        }
+
         if cli_matches.get_flag("update") {
             self.update = true;
         }
@@ -323,6 +612,10 @@ impl ToolproofParams {
             self.skip_hooks = true;
         }
 
+        if cli_matches.get_flag("no-parse-cache") {
+            self.no_parse_cache = true;
+        }
+
         if let Some(name) = cli_matches.get_one::<String>("name") {
             self.run_name = Some(name.clone());
         }
@@ -347,6 +640,14 @@ impl ToolproofParams {
             self.browser_timeout = *browser_timeout;
         }
 
+        if let Some(user_agent) = cli_matches.get_one::<String>("user-agent") {
+            self.user_agent = Some(user_agent.clone());
+        }
+
+        if cli_matches.get_flag("reuse-browser-context") {
+            self.reuse_browser_context = true;
+        }
+
         if let Some(placeholder_delimiter) = cli_matches.get_one::<String>("placeholder-delimiter")
         {
             self.placeholder_delimiter = placeholder_delimiter.clone();
@@ -359,7 +660,21 @@ impl ToolproofParams {
                     std::process::exit(1);
                 };
 
-                self.placeholders.insert(key.into(), value.into());
+                // Values starting with @ are read from a file, to keep secrets off the command line,
+                // while values starting with @@ are kept as a literal value starting with @
+                let value = match value.strip_prefix('@') {
+                    Some(literal) if literal.starts_with('@') => literal.to_string(),
+                    Some(filename) => match std::fs::read_to_string(filename) {
+                        Ok(contents) => contents.trim().to_string(),
+                        Err(e) => {
+                            eprintln!("Error reading the value of the {key} placeholder from {filename}: {e}");
+                            std::process::exit(1);
+                        }
+                    },
+                    None => value.to_string(),
+                };
+
+                self.placeholders.insert(key.into(), value);
             }
         }
 
@@ -373,8 +688,32 @@ impl ToolproofParams {
             self.retry_count = *retry_count;
         }
 
+        if let Some(repeat) = cli_matches.get_one::<usize>("repeat") {
+            self.repeat = *repeat;
+        }
+
+        if let Some(slowest) = cli_matches.get_one::<usize>("slowest") {
+            self.slowest = *slowest;
+        }
+
+        if let Some(retry_browser_steps) = cli_matches.get_one::<usize>("retry-browser-steps") {
+            self.retry_browser_steps = *retry_browser_steps;
+        }
+
         if cli_matches.get_flag("debugger") {
             self.debugger = true;
         }
+
+        if let Some(max_run_duration) = cli_matches.get_one::<u64>("max-run-duration") {
+            self.max_run_duration = Some(*max_run_duration);
+        }
+
+        if cli_matches.get_flag("keep-temp-on-failure") {
+            self.keep_temp_on_failure = true;
+        }
+
+        if let Some(log_file) = cli_matches.get_one::<PathBuf>("log-file") {
+            self.log_file = Some(log_file.clone());
+        }
     }
 }
@@ -1,13 +1,19 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 use path_slash::{PathBufExt, PathExt};
 use serde_json::{Map, Value};
+use similar_string::find_best_similarity;
 
 use crate::{
     errors::ToolproofInputError,
+    json_path::JsonPath,
     platforms::normalize_line_endings,
     segments::{ToolproofSegment, ToolproofSegments},
-    ToolproofMacroFile, ToolproofTestFile, ToolproofTestStep, ToolproofTestStepState,
+    ToolproofExtractTarget, ToolproofMacroFile, ToolproofTestFile, ToolproofTestStep,
+    ToolproofTestStepState,
 };
 
 struct ToolproofTestInput {
@@ -37,20 +43,197 @@ pub enum ToolproofPlatform {
     Windows,
     Mac,
     Linux,
+    #[serde(rename = "freebsd")]
+    FreeBsd,
+    /// Any Unix-family platform, including mac, linux, and freebsd
+    Unix,
+}
+
+const PLATFORM_NAMES: &[&str] = &["windows", "mac", "linux", "freebsd", "unix"];
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolproofArch {
+    X86_64,
+    #[serde(alias = "arm64")]
+    Aarch64,
+    X86,
+    Arm,
+}
+
+const ARCH_NAMES: &[&str] = &["x86_64", "aarch64", "arm64", "x86", "arm"];
+
+fn parse_named_values<T: serde::de::DeserializeOwned>(
+    values: Option<Vec<String>>,
+    names: &[&str],
+    invalid: impl Fn(String, String) -> ToolproofInputError,
+) -> Result<Option<Vec<T>>, ToolproofInputError> {
+    values
+        .map(|values| {
+            values
+                .into_iter()
+                .map(|value| {
+                    serde_json::from_value(Value::String(value.clone())).map_err(|_| {
+                        let closest = find_best_similarity(value.as_str(), names)
+                            .map(|s| s.0)
+                            .unwrap_or_else(|| "<nothing found>".to_string());
+                        invalid(value, closest)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()
+}
+
+fn parse_platforms(
+    platforms: Option<Vec<String>>,
+) -> Result<Option<Vec<ToolproofPlatform>>, ToolproofInputError> {
+    parse_named_values(platforms, PLATFORM_NAMES, |input, closest| {
+        ToolproofInputError::InvalidPlatform {
+            input,
+            closest,
+            available: PLATFORM_NAMES.join(", "),
+        }
+    })
+}
+
+fn parse_arch(
+    arch: Option<Vec<String>>,
+) -> Result<Option<Vec<ToolproofArch>>, ToolproofInputError> {
+    parse_named_values(arch, ARCH_NAMES, |input, closest| {
+        ToolproofInputError::InvalidArch {
+            input,
+            closest,
+            available: ARCH_NAMES.join(", "),
+        }
+    })
+}
+
+/// Steps without their own platforms or arch inherit those of the file they are defined in
+fn inherit_file_gates(
+    steps: &mut [ToolproofTestStep],
+    file_platforms: &Option<Vec<ToolproofPlatform>>,
+    file_arch: &Option<Vec<ToolproofArch>>,
+) {
+    use ToolproofTestStep::*;
+
+    for step in steps {
+        match step {
+            Ref {
+                platforms, arch, ..
+            }
+            | Macro {
+                platforms, arch, ..
+            }
+            | Instruction {
+                platforms, arch, ..
+            }
+            | Assertion {
+                platforms, arch, ..
+            }
+            | Extract {
+                platforms, arch, ..
+            }
+            | Snapshot {
+                platforms, arch, ..
+            } => {
+                if platforms.is_none() {
+                    *platforms = file_platforms.clone();
+                }
+                if arch.is_none() {
+                    *arch = file_arch.clone();
+                }
+            }
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct RawToolproofTestFile {
     name: String,
     r#type: Option<ToolproofFileType>,
-    platforms: Option<Vec<ToolproofPlatform>>,
-    steps: Vec<RawToolproofTestStep>,
+    platforms: Option<Vec<String>>,
+    arch: Option<Vec<String>>,
+    skip: Option<String>,
+    #[serde(default)]
+    quarantine: bool,
+    steps: Vec<RawToolproofStepEntry>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct RawToolproofMacroFile {
     r#macro: String,
-    steps: Vec<RawToolproofTestStep>,
+    platforms: Option<Vec<String>>,
+    arch: Option<Vec<String>>,
+    steps: Vec<RawToolproofStepEntry>,
+}
+
+/// An item in a list of steps, which can be a nested list of steps
+/// (e.g. an alias of a YAML anchor on a shared list of steps)
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum RawToolproofStepEntry {
+    Step(RawToolproofTestStep),
+    Steps(Vec<RawToolproofStepEntry>),
+}
+
+/// Flattens nested lists of steps into a single list
+fn flatten_steps(
+    entries: Vec<RawToolproofStepEntry>,
+    nested: bool,
+    steps: &mut Vec<ToolproofTestStep>,
+) -> Result<(), ToolproofInputError> {
+    for entry in entries {
+        match entry {
+            RawToolproofStepEntry::Step(step) => {
+                // Snapshots are written back to the step they came from,
+                // which is ambiguous if the step is shared
+                if let (true, RawToolproofTestStep::Snapshot { snapshot, .. }) = (nested, &step) {
+                    return Err(ToolproofInputError::NestedSnapshot {
+                        snapshot: snapshot.clone(),
+                    });
+                }
+                steps.push(step.try_into()?);
+            }
+            RawToolproofStepEntry::Steps(entries) => flatten_steps(entries, true, steps)?,
+        }
+    }
+    Ok(())
+}
+
+/// Step arguments, with any YAML merge keys (`<<: *anchor`) applied
+fn step_args(mut other: Map<String, Value>) -> HashMap<String, Value> {
+    apply_merge_keys(&mut other);
+    HashMap::from_iter(other)
+}
+
+fn apply_merge_keys(map: &mut Map<String, Value>) {
+    if let Some(merged) = map.remove("<<") {
+        let sources = match merged {
+            Value::Array(sources) => sources,
+            source => vec![source],
+        };
+        // Keys set directly take priority, followed by the earliest merged mapping
+        for source in sources {
+            if let Value::Object(source) = source {
+                for (key, value) in source {
+                    map.entry(key).or_insert(value);
+                }
+            }
+        }
+    }
+
+    for value in map.values_mut() {
+        apply_value_merge_keys(value);
+    }
+}
+
+fn apply_value_merge_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => apply_merge_keys(map),
+        Value::Array(values) => values.iter_mut().for_each(apply_value_merge_keys),
+        _ => {}
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -58,31 +241,50 @@ struct RawToolproofMacroFile {
 enum RawToolproofTestStep {
     Ref {
         r#ref: String,
-        platforms: Option<Vec<ToolproofPlatform>>,
+        platforms: Option<Vec<String>>,
+        arch: Option<Vec<String>>,
+        skip: Option<String>,
+        #[serde(flatten)]
+        other: Map<String, Value>,
     },
     Macro {
         r#macro: String,
-        platforms: Option<Vec<ToolproofPlatform>>,
+        platforms: Option<Vec<String>>,
+        arch: Option<Vec<String>>,
+        skip: Option<String>,
         #[serde(flatten)]
         other: Map<String, Value>,
     },
     BareStep(String),
     StepWithParams {
         step: String,
-        platforms: Option<Vec<ToolproofPlatform>>,
+        platforms: Option<Vec<String>>,
+        arch: Option<Vec<String>>,
+        skip: Option<String>,
+        timeout: Option<u64>,
+        #[serde(default)]
+        setup: bool,
         #[serde(flatten)]
         other: Map<String, Value>,
     },
     Snapshot {
         snapshot: String,
-        platforms: Option<Vec<ToolproofPlatform>>,
+        platforms: Option<Vec<String>>,
+        arch: Option<Vec<String>>,
+        skip: Option<String>,
+        timeout: Option<u64>,
         #[serde(flatten)]
         other: Map<String, Value>,
     },
     Extract {
         extract: String,
-        extract_location: String,
-        platforms: Option<Vec<ToolproofPlatform>>,
+        extract_location: Option<String>,
+        extract_placeholder: Option<String>,
+        extract_path: Option<String>,
+        platforms: Option<Vec<String>>,
+        arch: Option<Vec<String>>,
+        skip: Option<String>,
+        timeout: Option<u64>,
         #[serde(flatten)]
         other: Map<String, Value>,
     },
@@ -93,19 +295,26 @@ impl TryFrom<ToolproofTestInput> for ToolproofTestFile {
 
     fn try_from(value: ToolproofTestInput) -> Result<Self, Self::Error> {
         let mut steps = Vec::with_capacity(value.parsed.steps.len());
-        for step in value.parsed.steps {
-            steps.push(step.try_into()?);
-        }
+        flatten_steps(value.parsed.steps, false, &mut steps)?;
+
+        let platforms = parse_platforms(value.parsed.platforms)?;
+        let arch = parse_arch(value.parsed.arch)?;
+        inherit_file_gates(&mut steps, &platforms, &arch);
 
         Ok(ToolproofTestFile {
             name: value.parsed.name,
             r#type: value.parsed.r#type.unwrap_or(ToolproofFileType::Test),
-            platforms: value.parsed.platforms,
+            platforms,
+            arch,
+            skip: value.parsed.skip,
+            quarantine: value.parsed.quarantine,
             steps,
             original_source: value.original_source,
             file_path: value.file_path,
             file_directory: value.file_directory,
             failure_screenshot: None,
+            kept_temp_dir: None,
+            changed_images: vec![],
         })
     }
 }
@@ -115,9 +324,11 @@ impl TryFrom<ToolproofMacroInput> for ToolproofMacroFile {
 
     fn try_from(value: ToolproofMacroInput) -> Result<Self, Self::Error> {
         let mut steps = Vec::with_capacity(value.parsed.steps.len());
-        for step in value.parsed.steps {
-            steps.push(step.try_into()?);
-        }
+        flatten_steps(value.parsed.steps, false, &mut steps)?;
+
+        let platforms = parse_platforms(value.parsed.platforms)?;
+        let arch = parse_arch(value.parsed.arch)?;
+        inherit_file_gates(&mut steps, &platforms, &arch);
 
         Ok(ToolproofMacroFile {
             macro_segments: parse_segments(&value.parsed.r#macro)?,
@@ -135,60 +346,106 @@ impl TryFrom<RawToolproofTestStep> for ToolproofTestStep {
 
     fn try_from(value: RawToolproofTestStep) -> Result<Self, Self::Error> {
         match value {
-            RawToolproofTestStep::Ref { r#ref, platforms } => Ok(ToolproofTestStep::Ref {
+            RawToolproofTestStep::Ref {
+                r#ref,
+                platforms,
+                arch,
+                skip,
+                other,
+            } => Ok(ToolproofTestStep::Ref {
                 other_file: PathBuf::try_from(&r#ref)
                     .map_err(|_| ToolproofInputError::InvalidPath {
                         input: r#ref.clone(),
                     })?
                     .to_slash_lossy()
                     .into_owned(),
+                args: step_args(other),
                 orig: r#ref,
                 hydrated_steps: None,
                 state: ToolproofTestStepState::Dormant,
-                platforms,
+                platforms: parse_platforms(platforms)?,
+                arch: parse_arch(arch)?,
+                skip,
             }),
             RawToolproofTestStep::Macro {
                 r#macro,
                 platforms,
+                arch,
+                skip,
                 other,
             } => Ok(ToolproofTestStep::Macro {
                 step_macro: parse_segments(&r#macro)?,
-                args: HashMap::from_iter(other.into_iter()),
+                args: step_args(other),
                 orig: r#macro,
                 hydrated_steps: None,
                 state: ToolproofTestStepState::Dormant,
-                platforms,
+                platforms: parse_platforms(platforms)?,
+                arch: parse_arch(arch)?,
+                skip,
             }),
-            RawToolproofTestStep::BareStep(step) => parse_step(step, None, HashMap::new()),
+            RawToolproofTestStep::BareStep(step) => {
+                parse_step(step, None, None, None, None, false, HashMap::new())
+            }
             RawToolproofTestStep::StepWithParams {
                 step,
                 platforms,
+                arch,
+                skip,
+                timeout,
+                setup,
                 other,
-            } => parse_step(step, platforms, HashMap::from_iter(other.into_iter())),
+            } => parse_step(
+                step,
+                parse_platforms(platforms)?,
+                parse_arch(arch)?,
+                skip,
+                timeout,
+                setup,
+                step_args(other),
+            ),
             RawToolproofTestStep::Snapshot {
                 snapshot,
                 platforms,
+                arch,
+                skip,
+                timeout,
                 other,
             } => Ok(ToolproofTestStep::Snapshot {
                 snapshot: parse_segments(&snapshot)?,
                 snapshot_content: None,
-                args: HashMap::from_iter(other.into_iter()),
+                args: step_args(other),
                 orig: snapshot,
                 state: ToolproofTestStepState::Dormant,
-                platforms,
+                platforms: parse_platforms(platforms)?,
+                arch: parse_arch(arch)?,
+                skip,
+                timeout,
             }),
             RawToolproofTestStep::Extract {
                 extract,
                 extract_location,
+                extract_placeholder,
+                extract_path,
                 platforms,
+                arch,
+                skip,
+                timeout,
                 other,
             } => Ok(ToolproofTestStep::Extract {
                 extract: parse_segments(&extract)?,
-                extract_location,
-                args: HashMap::from_iter(other.into_iter()),
+                extract_target: match (extract_location, extract_placeholder) {
+                    (Some(location), None) => ToolproofExtractTarget::File(location),
+                    (None, Some(placeholder)) => ToolproofExtractTarget::Placeholder(placeholder),
+                    _ => return Err(ToolproofInputError::InvalidExtractTarget { extract }),
+                },
+                extract_path: extract_path.as_deref().map(JsonPath::parse).transpose()?,
+                args: step_args(other),
                 orig: extract,
                 state: ToolproofTestStepState::Dormant,
-                platforms,
+                platforms: parse_platforms(platforms)?,
+                arch: parse_arch(arch)?,
+                skip,
+                timeout,
             }),
         }
     }
@@ -197,16 +454,29 @@ impl TryFrom<RawToolproofTestStep> for ToolproofTestStep {
 fn parse_step(
     step: String,
     platforms: Option<Vec<ToolproofPlatform>>,
+    arch: Option<Vec<ToolproofArch>>,
+    skip: Option<String>,
+    timeout: Option<u64>,
+    setup: bool,
     args: HashMap<String, Value>,
 ) -> Result<ToolproofTestStep, ToolproofInputError> {
     if let Some((retrieval, assertion)) = step.split_once(" should ") {
+        if setup {
+            return Err(ToolproofInputError::SetupAssertion { step });
+        }
         Ok(ToolproofTestStep::Assertion {
             retrieval: parse_segments(retrieval)?,
-            assertion: parse_segments(assertion)?,
+            assertions: split_chained_assertions(assertion)
+                .into_iter()
+                .map(parse_segments)
+                .collect::<Result<_, _>>()?,
             args,
             orig: step,
             state: ToolproofTestStepState::Dormant,
             platforms,
+            arch,
+            skip,
+            timeout,
         })
     } else {
         Ok(ToolproofTestStep::Instruction {
@@ -215,17 +485,113 @@ fn parse_step(
             orig: step,
             state: ToolproofTestStepState::Dormant,
             platforms,
+            arch,
+            skip,
+            timeout,
+            setup,
         })
     }
 }
 
-pub fn parse_macro(s: &str, p: PathBuf) -> Result<ToolproofMacroFile, ToolproofInputError> {
-    let raw_macro = serde_yaml::from_str::<RawToolproofMacroFile>(s).map_err(|e| {
-        ToolproofInputError::ParseError {
-            filename: p.to_slash_lossy().into_owned(),
-            inner: e,
+/// Splits `contain "a" and contain "b"` into each assertion,
+/// ignoring any "and" within quoted values or arguments
+fn split_chained_assertions(s: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut start = 0;
+    let mut closing: Option<char> = None;
+
+    for (i, c) in s.char_indices() {
+        match closing {
+            Some(close) if c == close => closing = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => closing = Some(c),
+                '{' => closing = Some('}'),
+                ' ' if i >= start && s[i..].starts_with(" and ") => {
+                    parts.push(&s[start..i]);
+                    start = i + " and ".len();
+                }
+                _ => {}
+            },
         }
-    })?;
+    }
+    parts.push(&s[start..]);
+
+    parts
+}
+
+/// A retriever or assertion defined by the user in a `.toolproof.step.yml` file,
+/// implemented as JavaScript that runs in the browser
+#[derive(Debug, Clone, PartialEq)]
+pub enum ToolproofCustomStep {
+    Retriever {
+        segments: String,
+        js: String,
+        file_path: String,
+    },
+    Assertion {
+        segments: String,
+        js: String,
+        file_path: String,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum RawToolproofCustomStep {
+    Retriever { retriever: String, js: String },
+    Assertion { assertion: String, js: String },
+}
+
+/// Surfaces the line and column of a YAML error alongside the file path,
+/// rather than leaving it at the end of serde_yaml's message
+fn yaml_parse_error(p: &Path, e: serde_yaml::Error) -> ToolproofInputError {
+    let mut message = e.to_string();
+    let mut location = String::new();
+
+    if let Some(loc) = e.location() {
+        let suffix = format!(" at line {} column {}", loc.line(), loc.column());
+        if let Some(stripped) = message.strip_suffix(&suffix) {
+            message = stripped.to_string();
+        }
+        location = format!(":{}:{}", loc.line(), loc.column());
+    }
+
+    ToolproofInputError::ParseError {
+        filename: p.to_slash_lossy().into_owned(),
+        location,
+        message,
+    }
+}
+
+pub fn parse_custom_step(s: &str, p: PathBuf) -> Result<ToolproofCustomStep, ToolproofInputError> {
+    let raw_step =
+        serde_yaml::from_str::<RawToolproofCustomStep>(s).map_err(|e| yaml_parse_error(&p, e))?;
+    let file_path = p.to_slash_lossy().into_owned();
+
+    match raw_step {
+        RawToolproofCustomStep::Retriever { retriever, js } => {
+            parse_segments(&retriever)?;
+            Ok(ToolproofCustomStep::Retriever {
+                segments: retriever,
+                js,
+                file_path,
+            })
+        }
+        RawToolproofCustomStep::Assertion { assertion, js } => {
+            parse_segments(&assertion)?;
+            Ok(ToolproofCustomStep::Assertion {
+                segments: assertion,
+                js,
+                file_path,
+            })
+        }
+    }
+}
+
+pub fn parse_macro(s: &str, p: PathBuf) -> Result<ToolproofMacroFile, ToolproofInputError> {
+    let raw_macro =
+        serde_yaml::from_str::<RawToolproofMacroFile>(s).map_err(|e| yaml_parse_error(&p, e))?;
 
     ToolproofMacroInput {
         parsed: raw_macro,
@@ -240,12 +606,8 @@ pub fn parse_macro(s: &str, p: PathBuf) -> Result<ToolproofMacroFile, ToolproofI
 }
 
 pub fn parse_file(s: &str, p: PathBuf) -> Result<ToolproofTestFile, ToolproofInputError> {
-    let raw_test = serde_yaml::from_str::<RawToolproofTestFile>(s).map_err(|e| {
-        ToolproofInputError::ParseError {
-            filename: p.to_slash_lossy().into_owned(),
-            inner: e,
-        }
-    })?;
+    let raw_test =
+        serde_yaml::from_str::<RawToolproofTestFile>(s).map_err(|e| yaml_parse_error(&p, e))?;
 
     ToolproofTestInput {
         parsed: raw_test,
@@ -378,7 +740,15 @@ mod test {
 
     #[test]
     fn test_parsing_steps() {
-        let Ok(step) = parse_step("I have a {js} file".to_string(), None, HashMap::new()) else {
+        let Ok(step) = parse_step(
+            "I have a {js} file".to_string(),
+            None,
+            None,
+            None,
+            None,
+            false,
+            HashMap::new(),
+        ) else {
             panic!("Step did not parse");
         };
 
@@ -395,13 +765,21 @@ mod test {
                 args: HashMap::new(),
                 orig: "I have a {js} file".to_string(),
                 state: ToolproofTestStepState::Dormant,
-                platforms: None
+                platforms: None,
+                arch: None,
+                skip: None,
+                timeout: None,
+                setup: false
             }
         );
 
         let Ok(step) = parse_step(
             "The file {name} should contain {html}".to_string(),
             None,
+            None,
+            None,
+            None,
+            false,
             HashMap::new(),
         ) else {
             panic!("Step did not parse");
@@ -416,16 +794,170 @@ mod test {
                         Variable("name".to_string())
                     ]
                 },
-                assertion: ToolproofSegments {
+                assertions: vec![ToolproofSegments {
                     segments: vec![
                         Literal("contain ".to_string()),
                         Variable("html".to_string()),
                     ]
-                },
+                }],
                 args: HashMap::new(),
                 orig: "The file {name} should contain {html}".to_string(),
                 state: ToolproofTestStepState::Dormant,
-                platforms: None
+                platforms: None,
+                arch: None,
+                skip: None,
+                timeout: None
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_chained_assertions() {
+        let Ok(ToolproofTestStep::Assertion { assertions, .. }) = parse_step(
+            "stdout should contain \"cats and dogs\" and not contain {other} and be exactly 'a and b'"
+                .to_string(),
+            None,
+            None,
+            None,
+            None,
+            false,
+            HashMap::new(),
+        ) else {
+            panic!("Step did not parse as an assertion");
+        };
+
+        assert_eq!(
+            assertions,
+            vec![
+                ToolproofSegments {
+                    segments: vec![Literal("contain ".to_string()), Value(st("cats and dogs"))]
+                },
+                ToolproofSegments {
+                    segments: vec![
+                        Literal("not contain ".to_string()),
+                        Variable("other".to_string())
+                    ]
+                },
+                ToolproofSegments {
+                    segments: vec![Literal("be exactly ".to_string()), Value(st("a and b"))]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_steps_inherit_file_platforms() {
+        let file = parse_file(
+            "name: Platform test\nplatforms: [linux]\nsteps:\n  - I run \"ls\"\n  - step: I run \"dir\"\n    platforms: [windows]\n",
+            PathBuf::from("test.toolproof.yml"),
+        )
+        .expect("Valid file");
+
+        let platforms = file
+            .steps
+            .iter()
+            .map(|step| match step {
+                ToolproofTestStep::Instruction { platforms, .. } => platforms.clone(),
+                _ => panic!("Expected an instruction"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            platforms,
+            vec![
+                Some(vec![ToolproofPlatform::Linux]),
+                Some(vec![ToolproofPlatform::Windows])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_platform_suggests_closest() {
+        let Err(ToolproofInputError::InvalidPlatform { input, closest, .. }) = parse_file(
+            "name: Platform test\nsteps:\n  - step: I run \"ls\"\n    platforms: [macos]\n",
+            PathBuf::from("test.toolproof.yml"),
+        ) else {
+            panic!("Expected an invalid platform error");
+        };
+
+        assert_eq!(input, "macos");
+        assert_eq!(closest, "mac");
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let Err(ToolproofInputError::ParseError {
+            filename, location, ..
+        }) = parse_file(
+            "name: Broken test\nsteps:\n  - I run \"ls\"\n  - step: [unclosed\n",
+            PathBuf::from("broken.toolproof.yml"),
+        )
+        else {
+            panic!("Expected a parse error");
+        };
+
+        assert_eq!(filename, "broken.toolproof.yml");
+        assert!(location.starts_with(':'), "location was {location:?}");
+    }
+
+    #[test]
+    fn test_yaml_anchors_expand() {
+        let file = parse_file(
+            "name: Anchor test\nshared: &setup\n  - I run \"ls\"\n  - step: I run {cmd}\n    <<: &args\n      cmd: pwd\nsteps:\n  - *setup\n  - step: I run {cmd}\n    <<: *args\n  - *setup\n",
+            PathBuf::from("test.toolproof.yml"),
+        )
+        .expect("Valid file");
+
+        let steps = file
+            .steps
+            .iter()
+            .map(|step| match step {
+                ToolproofTestStep::Instruction { orig, args, .. } => {
+                    (orig.as_str(), args.get("cmd").cloned())
+                }
+                _ => panic!("Expected an instruction"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            steps,
+            vec![
+                ("I run \"ls\"", None),
+                ("I run {cmd}", Some(st("pwd"))),
+                ("I run {cmd}", Some(st("pwd"))),
+                ("I run \"ls\"", None),
+                ("I run {cmd}", Some(st("pwd")))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arm64_arch_alias() {
+        let Ok(file) = parse_file(
+            "name: Arch test\narch: [arm64]\nsteps:\n  - I run \"ls\"\n",
+            PathBuf::from("test.toolproof.yml"),
+        ) else {
+            panic!("File did not parse");
+        };
+
+        assert_eq!(file.arch, Some(vec![ToolproofArch::Aarch64]));
+    }
+
+    #[test]
+    fn test_parsing_custom_steps() {
+        let Ok(step) = parse_custom_step(
+            "assertion: be a valid {kind}\njs: toolproof.assert(value.includes(args.kind));\n",
+            PathBuf::from("kind.toolproof.step.yml"),
+        ) else {
+            panic!("Custom step did not parse");
+        };
+
+        assert_eq!(
+            step,
+            ToolproofCustomStep::Assertion {
+                segments: "be a valid {kind}".to_string(),
+                js: "toolproof.assert(value.includes(args.kind));".to_string(),
+                file_path: "kind.toolproof.step.yml".to_string(),
             }
         );
     }
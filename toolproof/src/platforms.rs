@@ -1,22 +1,120 @@
 use std::env;
 
-use crate::parser::ToolproofPlatform;
+use crate::parser::{ToolproofArch, ToolproofPlatform};
 
 pub fn normalize_line_endings(s: impl AsRef<str>) -> String {
     s.as_ref().replace("\r\n", "\n")
 }
 
+/// Converts normalized line endings back to CRLF if the original content used them,
+/// so that rewriting a file on Windows doesn't change every line of it
+pub fn match_line_endings(s: impl AsRef<str>, original: impl AsRef<str>) -> String {
+    let s = normalize_line_endings(s);
+    if original.as_ref().contains("\r\n") {
+        s.replace('\n', "\r\n")
+    } else {
+        s
+    }
+}
+
 pub fn platform_matches(platforms: &Option<Vec<ToolproofPlatform>>) -> bool {
+    platform_matches_os(platforms, env::consts::OS, env::consts::FAMILY)
+}
+
+fn platform_matches_os(platforms: &Option<Vec<ToolproofPlatform>>, os: &str, family: &str) -> bool {
     let Some(platforms) = platforms else {
         return true;
     };
     if platforms.is_empty() {
         return true;
     }
-    match env::consts::OS {
+    if family == "unix" && platforms.contains(&ToolproofPlatform::Unix) {
+        return true;
+    }
+    match os {
         "linux" => platforms.contains(&ToolproofPlatform::Linux),
         "macos" => platforms.contains(&ToolproofPlatform::Mac),
         "windows" => platforms.contains(&ToolproofPlatform::Windows),
+        "freebsd" => platforms.contains(&ToolproofPlatform::FreeBsd),
         _ => false,
     }
 }
+
+pub fn arch_matches(arch: &Option<Vec<ToolproofArch>>) -> bool {
+    arch_matches_current(arch, env::consts::ARCH)
+}
+
+fn arch_matches_current(arch: &Option<Vec<ToolproofArch>>, current: &str) -> bool {
+    let Some(arch) = arch else {
+        return true;
+    };
+    if arch.is_empty() {
+        return true;
+    }
+    match current {
+        "x86_64" => arch.contains(&ToolproofArch::X86_64),
+        "aarch64" => arch.contains(&ToolproofArch::Aarch64),
+        "x86" => arch.contains(&ToolproofArch::X86),
+        "arm" => arch.contains(&ToolproofArch::Arm),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_platforms_matching_the_os() {
+        use ToolproofPlatform::*;
+
+        assert!(platform_matches_os(&None, "linux", "unix"));
+        assert!(platform_matches_os(&Some(vec![]), "windows", "windows"));
+
+        assert!(platform_matches_os(&Some(vec![Linux]), "linux", "unix"));
+        assert!(platform_matches_os(&Some(vec![Mac]), "macos", "unix"));
+        assert!(platform_matches_os(&Some(vec![FreeBsd]), "freebsd", "unix"));
+        assert!(!platform_matches_os(&Some(vec![Linux]), "freebsd", "unix"));
+        assert!(!platform_matches_os(&Some(vec![FreeBsd]), "linux", "unix"));
+
+        // The unix platform covers every Unix-family OS, including ones without their own platform
+        assert!(platform_matches_os(&Some(vec![Unix]), "linux", "unix"));
+        assert!(platform_matches_os(&Some(vec![Unix]), "macos", "unix"));
+        assert!(platform_matches_os(&Some(vec![Unix]), "freebsd", "unix"));
+        assert!(platform_matches_os(&Some(vec![Unix]), "openbsd", "unix"));
+        assert!(!platform_matches_os(
+            &Some(vec![Unix]),
+            "windows",
+            "windows"
+        ));
+        assert!(platform_matches_os(
+            &Some(vec![Unix, Windows]),
+            "windows",
+            "windows"
+        ));
+    }
+
+    #[test]
+    fn test_arch_matching_the_current_arch() {
+        use ToolproofArch::*;
+
+        assert!(arch_matches_current(&None, "x86_64"));
+        assert!(arch_matches_current(&Some(vec![]), "aarch64"));
+
+        assert!(arch_matches_current(&Some(vec![X86_64]), "x86_64"));
+        assert!(arch_matches_current(&Some(vec![Aarch64]), "aarch64"));
+        assert!(arch_matches_current(&Some(vec![X86]), "x86"));
+        assert!(arch_matches_current(&Some(vec![Arm]), "arm"));
+        assert!(arch_matches_current(
+            &Some(vec![X86_64, Aarch64]),
+            "aarch64"
+        ));
+        assert!(!arch_matches_current(&Some(vec![X86_64]), "aarch64"));
+        assert!(!arch_matches_current(&Some(vec![Arm]), "aarch64"));
+        assert!(!arch_matches_current(&Some(vec![X86_64]), "riscv64"));
+
+        // arm64 is read as another name for aarch64
+        let arch: Vec<ToolproofArch> = serde_yaml::from_str("[arm64]").unwrap();
+        assert!(arch_matches_current(&Some(arch), "aarch64"));
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use console::Term;
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::options::ToolproofParams;
+
+/// Shows how far through a run we are, when running in a terminal.
+/// Hidden for porcelain and verbose output, where it would get in the way.
+#[derive(Clone)]
+pub struct RunProgress {
+    bar: ProgressBar,
+    failing: Arc<AtomicUsize>,
+}
+
+impl RunProgress {
+    pub fn new(params: &ToolproofParams) -> Self {
+        let show = !params.porcelain
+            && !params.verbose
+            && !params.debugger
+            && !params.interactive
+            && Term::stdout().is_term();
+
+        let bar = if show {
+            let bar = ProgressBar::new(0);
+            bar.set_style(
+                ProgressStyle::with_template("{bar:40.cyan/blue} {pos}/{len} tests {msg}")
+                    .expect("progress template should be valid"),
+            );
+            bar
+        } else {
+            ProgressBar::hidden()
+        };
+
+        Self {
+            bar,
+            failing: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    pub fn add_test(&self) {
+        self.bar.inc_length(1);
+    }
+
+    /// Logs the result of a test above the progress bar, and counts it as complete
+    pub fn complete<T, E>(&self, log_result: impl FnOnce() -> Result<T, E>) -> Result<T, E> {
+        let res = self.bar.suspend(log_result);
+
+        if res.is_err() {
+            let failing = self.failing.fetch_add(1, Ordering::SeqCst) + 1;
+            self.bar.set_message(format!("({failing} failing)"));
+        }
+        self.bar.inc(1);
+
+        res
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
@@ -10,40 +10,223 @@ use std::{
     sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
-use tokio::time::{self, Duration};
+use tokio::time::{self, error::Elapsed, Duration};
 
 use crate::{
-    civilization::Civilization,
-    definitions::{browser::screenshots::ScreenshotViewport, ToolproofInstruction},
+    civilization::{unique_test_id, Civilization},
+    definitions::{
+        browser::{screenshots::ScreenshotViewport, BrowserEmulation},
+        ToolproofAssertion, ToolproofInstruction, ToolproofRetriever,
+    },
     errors::{ToolproofInputError, ToolproofStepError, ToolproofTestError, ToolproofTestFailure},
-    platforms::platform_matches,
-    segments::SegmentArgs,
+    json_path::JsonPath,
+    platforms::{arch_matches, platform_matches},
+    segments::{AssertionWrapper, SegmentArgs, ToolproofSegments},
     universe::Universe,
-    ToolproofTestFile, ToolproofTestStep, ToolproofTestStepState, ToolproofTestSuccess,
+    ToolproofExtractTarget, ToolproofTestFile, ToolproofTestStep, ToolproofTestStepState,
+    ToolproofTestSuccess,
 };
 
+/// How long to wait before running a failed browser step again
+const BROWSER_STEP_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// How many more times a step should be run if it fails. Only browser steps are retried,
+/// as they can fail intermittently where other steps would fail the same way every time.
+fn step_retries(segments: &ToolproofSegments, civ: &Civilization) -> usize {
+    if segments.get_as_string().starts_with("in my browser") {
+        civ.universe.ctx.params.retry_browser_steps
+    } else {
+        0
+    }
+}
+
+/// Whether a failed step might pass if it was run again,
+/// rather than being a mistake in how the step was written
+fn is_retryable(e: &ToolproofStepError) -> bool {
+    !matches!(e, ToolproofStepError::External(_))
+}
+
+/// Runs an instruction, running it again after a short delay if it fails and can be retried.
+/// Each attempt has its own timeout, and attempts that time out are not retried.
+async fn run_instruction(
+    instruction: &dyn ToolproofInstruction,
+    args: &SegmentArgs<'_>,
+    retries: usize,
+    timeout_dur: Duration,
+    civ: &mut Civilization<'_>,
+) -> Result<Result<(), ToolproofStepError>, Elapsed> {
+    let mut attempt = 0;
+    loop {
+        match time::timeout(timeout_dur, instruction.run(args, civ)).await {
+            Ok(Err(e)) if attempt < retries && is_retryable(&e) => {
+                attempt += 1;
+                time::sleep(BROWSER_STEP_RETRY_DELAY).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Runs a retrieval, running it again after a short delay if it fails and can be retried.
+/// Each attempt has its own timeout, and attempts that time out are not retried.
+async fn run_retrieval(
+    retrieval: &dyn ToolproofRetriever,
+    args: &SegmentArgs<'_>,
+    retries: usize,
+    timeout_dur: Duration,
+    civ: &mut Civilization<'_>,
+) -> Result<Result<serde_json::Value, ToolproofStepError>, Elapsed> {
+    let mut attempt = 0;
+    loop {
+        match time::timeout(timeout_dur, retrieval.run(args, civ)).await {
+            Ok(Err(e)) if attempt < retries && is_retryable(&e) => {
+                attempt += 1;
+                time::sleep(BROWSER_STEP_RETRY_DELAY).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Describes an assertion along with the wrappers around it, as it was written in the step
+fn describe_wrapped_assertion(wrappers: &[AssertionWrapper], assertion_name: &str) -> String {
+    wrappers
+        .iter()
+        .map(AssertionWrapper::prefix)
+        .chain(std::iter::once(assertion_name))
+        .collect()
+}
+
+/// Runs an assertion, applying each wrapper around it from the outermost inwards
+#[async_recursion]
+async fn run_wrapped_assertion(
+    wrappers: &[AssertionWrapper],
+    assertion_name: &str,
+    assertion_step: &dyn ToolproofAssertion,
+    assertion_args: &SegmentArgs<'_>,
+    value: serde_json::Value,
+    civ: &mut Civilization<'_>,
+) -> Result<(), ToolproofStepError> {
+    let Some((wrapper, inner)) = wrappers.split_first() else {
+        return assertion_step.run(value, assertion_args, civ).await;
+    };
+    let inner_name = describe_wrapped_assertion(inner, assertion_name);
+
+    if *wrapper == AssertionWrapper::Not {
+        return match run_wrapped_assertion(
+            inner,
+            assertion_name,
+            assertion_step,
+            assertion_args,
+            value.clone(),
+            civ,
+        )
+        .await
+        {
+            Ok(_) => Err(ToolproofStepError::Assertion(
+                ToolproofTestFailure::Custom {
+                    msg: format!(
+                        "The value\n---\n{}\n---\nshould not {inner_name}, but does",
+                        serde_json::to_string(&value).expect("should be yaml-able"),
+                    ),
+                },
+            )),
+            Err(ToolproofStepError::Assertion(_)) => Ok(()),
+            Err(e) => Err(e),
+        };
+    }
+
+    let serde_json::Value::Array(items) = &value else {
+        return Err(ToolproofInputError::StepRequirementsNotMet {
+            reason: format!(
+                "Only arrays have items to check, but received the value\n---\n{}\n---",
+                serde_json::to_string(&value).expect("should be yaml-able"),
+            ),
+        }
+        .into());
+    };
+
+    for (index, item) in items.iter().enumerate() {
+        let res = run_wrapped_assertion(
+            inner,
+            assertion_name,
+            assertion_step,
+            assertion_args,
+            item.clone(),
+            civ,
+        )
+        .await;
+
+        match res {
+            Ok(_) if *wrapper == AssertionWrapper::AnyItem => return Ok(()),
+            Err(ToolproofStepError::Assertion(failure))
+                if *wrapper == AssertionWrapper::AllItems =>
+            {
+                let msg = format!(
+                    "Item {index} of the array\n---\n{}\n---\nshould {inner_name}, but does not:\n{failure}",
+                    serde_json::to_string(&value).expect("should be yaml-able"),
+                );
+                return Err(ToolproofTestFailure::Custom { msg }.into());
+            }
+            Ok(_) | Err(ToolproofStepError::Assertion(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    if *wrapper == AssertionWrapper::AnyItem {
+        let msg = format!(
+            "The array\n---\n{}\n---\nshould have any item {inner_name}, but no item does",
+            serde_json::to_string(&value).expect("should be yaml-able"),
+        );
+        return Err(ToolproofTestFailure::Custom { msg }.into());
+    }
+
+    Ok(())
+}
+
 pub async fn run_toolproof_experiment(
     input: &mut ToolproofTestFile,
     universe: Arc<Universe<'_>>,
 ) -> Result<ToolproofTestSuccess, ToolproofTestError> {
-    if !platform_matches(&input.platforms) {
+    if !platform_matches(&input.platforms) || !arch_matches(&input.arch) || input.skip.is_some() {
         return Ok(ToolproofTestSuccess::Skipped);
     }
 
     let mut civ = Civilization {
         tmp_dir: None,
+        unique_id: unique_test_id(),
         last_command_output: None,
+        last_response: None,
         assigned_server_port: None,
+        serving_https: false,
+        proxies: vec![],
         window: None,
+        emulation: BrowserEmulation::new(&universe.ctx.params),
         threads: vec![],
         handles: vec![],
-        env_vars: HashMap::new(),
+        env_vars: universe.ctx.params.env.clone(),
+        file_directory: PathBuf::from(&input.file_directory),
+        step_timeout: universe.ctx.params.timeout,
+        changed_images: vec![],
         universe,
     };
 
-    let res = run_toolproof_steps(&input.file_directory, &mut input.steps, &mut civ, None).await;
-
-    if res.is_err() && civ.window.is_some() {
+    let include_chain = vec![PathBuf::from(&input.file_path)
+        .normalize()
+        .to_string_lossy()
+        .into_owned()];
+    let res = run_toolproof_steps(
+        &input.file_directory,
+        &mut input.steps,
+        &mut civ,
+        &mut HashMap::new(),
+        &include_chain,
+    )
+    .await;
+
+    // A failed setup step means the test never got as far as the browser state worth capturing
+    let failed_in_test = res.as_ref().is_err_and(|e| !e.step.is_setup());
+    if failed_in_test && civ.window.is_some() {
         if let Some(screenshot_target) = &civ.universe.ctx.params.failure_screenshot_location {
             let instruction = ScreenshotViewport {};
             let filename = format!(
@@ -75,6 +258,14 @@ pub async fn run_toolproof_experiment(
         }
     }
 
+    input.changed_images = std::mem::take(&mut civ.changed_images);
+
+    if res.is_err() && civ.universe.ctx.params.keep_temp_on_failure {
+        if let Some(tmp_dir) = civ.tmp_dir.take() {
+            input.kept_temp_dir = Some(tmp_dir.keep());
+        }
+    }
+
     civ.shutdown().await;
 
     res
@@ -94,7 +285,8 @@ fn debugger_pause(step: &ToolproofTestStep, civ: &Civilization) {
     }
 
     if let Some(port) = civ.assigned_server_port {
-        println!("Server hosted at: http://localhost:{}", port);
+        let scheme = if civ.serving_https { "https" } else { "http" };
+        println!("Server hosted at: {scheme}://localhost:{port}");
     }
 
     println!("\nNext:");
@@ -111,16 +303,93 @@ fn debugger_pause(step: &ToolproofTestStep, civ: &Civilization) {
     let _ = term.read_line();
 }
 
+/// Narrows an extracted value down to the value at `path`,
+/// parsing string values (such as the contents of a file) as JSON first
+fn select_extract_path(
+    path: &JsonPath,
+    value: serde_json::Value,
+) -> Result<serde_json::Value, ToolproofStepError> {
+    let value = match value {
+        serde_json::Value::String(s) => {
+            serde_json::from_str(&s).map_err(|e| ToolproofTestFailure::Custom {
+                msg: format!("Unable to select {path}, as the value is not valid JSON: {e}"),
+            })?
+        }
+        value => value,
+    };
+
+    path.select(&value)
+        .cloned()
+        .map_err(|msg| ToolproofTestFailure::Custom { msg }.into())
+}
+
+/// Adds a file or macro to the chain of includes being run,
+/// erroring if it is already being run further up the chain
+/// or if the chain is nested deeper than `max_depth`
+fn extend_include_chain(
+    include_chain: &[String],
+    next: String,
+    max_depth: usize,
+) -> Result<Vec<String>, ToolproofInputError> {
+    if let Some(start) = include_chain.iter().position(|i| *i == next) {
+        let mut cycle = include_chain[start..].to_vec();
+        cycle.push(next);
+        return Err(ToolproofInputError::IncludeCycle {
+            cycle: cycle.join(" -> "),
+        });
+    }
+
+    let mut include_chain = include_chain.to_vec();
+    include_chain.push(next);
+
+    // The first entry is the test file itself, which isn't nested
+    if include_chain.len() - 1 > max_depth {
+        return Err(ToolproofInputError::NestingTooDeep {
+            max_depth,
+            chain: include_chain.join(" -> "),
+        });
+    }
+
+    Ok(include_chain)
+}
+
+/// Passes the placeholders from a ref or macro back to the steps that included it,
+/// so that values extracted inside it can be used afterwards. Its own arguments stay inside it.
+fn return_extracted_placeholders<'a>(
+    inner_placeholders: HashMap<String, String>,
+    inner_args: impl Iterator<Item = &'a String>,
+    transient_placeholders: &mut HashMap<String, String>,
+) {
+    let inner_args = inner_args.collect::<Vec<_>>();
+    transient_placeholders.extend(
+        inner_placeholders
+            .into_iter()
+            .filter(|(name, _)| !inner_args.iter().any(|arg| *arg == name)),
+    );
+}
+
 #[async_recursion]
 async fn run_toolproof_steps(
     file_directory: &String,
     steps: &mut Vec<ToolproofTestStep>,
     civ: &mut Civilization<'_>,
-    transient_placeholders: Option<HashMap<String, String>>,
+    transient_placeholders: &mut HashMap<String, String>,
+    include_chain: &[String],
 ) -> Result<ToolproofTestSuccess, ToolproofTestError> {
-    let timeout_mins = civ.universe.ctx.params.timeout;
-    let timeout_dur = Duration::from_secs(timeout_mins);
     for cur_step in steps.iter_mut() {
+        civ.file_directory = PathBuf::from(file_directory);
+
+        if cur_step.skip_reason().is_some() {
+            cur_step.set_state(ToolproofTestStepState::Skipped);
+            continue;
+        }
+
+        let timeout_secs = cur_step
+            .timeout()
+            .unwrap_or(civ.universe.ctx.params.timeout);
+        let timeout_dur = Duration::from_secs(timeout_secs);
+        civ.step_timeout = timeout_secs;
+
         let marked_base_step = cur_step.clone();
         let marked_base_args = cur_step.args_pretty();
 
@@ -137,7 +406,7 @@ async fn run_toolproof_steps(
             *state = ToolproofTestStepState::Failed;
             ToolproofTestError {
                 err: ToolproofStepError::Assertion(ToolproofTestFailure::Custom {
-                    msg: format!("Step timed out after {timeout_mins}s"),
+                    msg: format!("Step timed out after {timeout_secs}s"),
                 }),
                 step: marked_base_step.clone(),
                 arg_str: marked_base_args.clone(),
@@ -147,10 +416,13 @@ async fn run_toolproof_steps(
         match cur_step {
             crate::ToolproofTestStep::Ref {
                 other_file,
+                args,
                 orig: _,
                 hydrated_steps,
                 state,
                 platforms,
+                arch,
+                ..
             } => {
                 debugger_pause(&marked_base_step, civ);
 
@@ -171,18 +443,45 @@ async fn run_toolproof_steps(
                     ));
                 };
 
+                let include_chain = extend_include_chain(
+                    include_chain,
+                    target_path,
+                    civ.universe.ctx.params.max_nesting_depth,
+                )
+                .map_err(|e| mark_and_return_step_error(e.into(), state))?;
+
+                let ref_args =
+                    SegmentArgs::build_from_args(args, Some(&civ), Some(&*transient_placeholders));
+
+                // Steps inside the ref can use placeholders extracted before it
+                let mut ref_placeholders = transient_placeholders.clone();
+                for name in args.keys() {
+                    match ref_args.get_string(name) {
+                        Ok(res) => {
+                            ref_placeholders.insert(name.clone(), res);
+                        }
+                        Err(e) => return Err(mark_and_return_step_error(e.into(), state)),
+                    }
+                }
+
                 *hydrated_steps = Some(target_file.steps);
 
-                if platform_matches(platforms) {
+                if platform_matches(platforms) && arch_matches(arch) {
                     match run_toolproof_steps(
                         &target_file.file_directory,
                         hydrated_steps.as_mut().unwrap(),
                         civ,
-                        None,
+                        &mut ref_placeholders,
+                        &include_chain,
                     )
                     .await
                     {
                         Ok(_) => {
+                            return_extracted_placeholders(
+                                ref_placeholders,
+                                args.keys(),
+                                transient_placeholders,
+                            );
                             *state = ToolproofTestStepState::Passed;
                         }
                         Err(e) => {
@@ -201,6 +500,8 @@ async fn run_toolproof_steps(
                 hydrated_steps,
                 state,
                 platforms,
+                arch,
+                ..
             } => {
                 debugger_pause(&marked_base_step, civ);
 
@@ -221,12 +522,13 @@ async fn run_toolproof_steps(
                     step_macro,
                     args,
                     Some(&civ),
-                    transient_placeholders.as_ref(),
+                    Some(&*transient_placeholders),
                 )
                 .map_err(|e| mark_and_return_step_error(e.into(), state))?;
 
-                let mut macro_placeholders = HashMap::with_capacity(variable_names.len());
-                for name in variable_names {
+                // Steps inside the macro can use placeholders extracted before it
+                let mut macro_placeholders = transient_placeholders.clone();
+                for name in variable_names.iter().cloned() {
                     match macro_args.get_string(&name) {
                         Ok(res) => {
                             macro_placeholders.insert(name, res);
@@ -235,18 +537,31 @@ async fn run_toolproof_steps(
                     }
                 }
 
+                let include_chain = extend_include_chain(
+                    include_chain,
+                    format!("macro \"{}\"", defined_macro.macro_orig),
+                    civ.universe.ctx.params.max_nesting_depth,
+                )
+                .map_err(|e| mark_and_return_step_error(e.into(), state))?;
+
                 *hydrated_steps = Some(defined_macro.steps.clone());
 
-                if platform_matches(platforms) {
+                if platform_matches(platforms) && arch_matches(arch) {
                     match run_toolproof_steps(
                         &defined_macro.file_directory,
                         hydrated_steps.as_mut().unwrap(),
                         civ,
-                        Some(macro_placeholders),
+                        &mut macro_placeholders,
+                        &include_chain,
                     )
                     .await
                     {
                         Ok(_) => {
+                            return_extracted_placeholders(
+                                macro_placeholders,
+                                variable_names.iter(),
+                                transient_placeholders,
+                            );
                             *state = ToolproofTestStepState::Passed;
                         }
                         Err(e) => {
@@ -264,6 +579,7 @@ async fn run_toolproof_steps(
 
                 state,
                 platforms,
+                arch,
                 ..
             } => {
                 debugger_pause(&marked_base_step, civ);
@@ -283,12 +599,20 @@ async fn run_toolproof_steps(
                     step,
                     args,
                     Some(&civ),
-                    transient_placeholders.as_ref(),
+                    Some(&*transient_placeholders),
                 )
                 .map_err(|e| mark_and_return_step_error(e.into(), state))?;
+                let retries = step_retries(reference_segments, civ);
 
-                if platform_matches(platforms) {
-                    match time::timeout(timeout_dur, instruction.run(&instruction_args, civ)).await
+                if platform_matches(platforms) && arch_matches(arch) {
+                    match run_instruction(
+                        *instruction,
+                        &instruction_args,
+                        retries,
+                        timeout_dur,
+                        civ,
+                    )
+                    .await
                     {
                         Ok(Ok(_)) => {}
                         Ok(Err(e)) => {
@@ -306,11 +630,12 @@ async fn run_toolproof_steps(
             }
             crate::ToolproofTestStep::Assertion {
                 retrieval,
-                assertion,
+                assertions,
                 args,
 
                 state,
                 platforms,
+                arch,
                 ..
             } => {
                 debugger_pause(&marked_base_step, civ);
@@ -329,12 +654,14 @@ async fn run_toolproof_steps(
                     retrieval,
                     args,
                     Some(&civ),
-                    transient_placeholders.as_ref(),
+                    Some(&*transient_placeholders),
                 )
                 .map_err(|e| mark_and_return_step_error(e.into(), state))?;
+                let retries = step_retries(reference_ret, civ);
 
-                let value = if platform_matches(platforms) {
-                    match time::timeout(timeout_dur, retrieval_step.run(&retrieval_args, civ)).await
+                let value = if platform_matches(platforms) && arch_matches(arch) {
+                    match run_retrieval(*retrieval_step, &retrieval_args, retries, timeout_dur, civ)
+                        .await
                     {
                         Ok(Ok(val)) => val,
                         Ok(Err(e)) => {
@@ -348,37 +675,69 @@ async fn run_toolproof_steps(
                     serde_json::Value::Null
                 };
 
-                let Some((reference_assert, assertion_step)) =
-                    civ.universe.assertions.get_key_value(assertion)
-                else {
-                    return Err(mark_and_return_step_error(
-                        ToolproofStepError::External(ToolproofInputError::NonexistentStep),
-                        state,
-                    ));
-                };
-
-                let assertion_args = SegmentArgs::build(
-                    reference_assert,
-                    assertion,
-                    args,
-                    Some(&civ),
-                    transient_placeholders.as_ref(),
-                )
-                .map_err(|e| mark_and_return_step_error(e.into(), state))?;
+                // Assertions phrased as `not <assertion>` (or another wrapper) that aren't
+                // registered themselves are run as the wrapped assertion
+                let resolved_assertions = assertions
+                    .iter()
+                    .map(|assertion| {
+                        assertion.unwrap_assertion(|a| civ.universe.assertions.contains_key(a))
+                    })
+                    .collect::<Vec<_>>();
+
+                // Every assertion is resolved before any run, so that a typo in a later
+                // assertion isn't hidden by an earlier one failing
+                let mut assertion_steps = Vec::with_capacity(assertions.len());
+                for (assertion, wrappers) in resolved_assertions.iter() {
+                    let Some((reference_assert, assertion_step)) =
+                        civ.universe.assertions.get_key_value(assertion)
+                    else {
+                        return Err(mark_and_return_step_error(
+                            ToolproofStepError::External(ToolproofInputError::NonexistentStep),
+                            state,
+                        ));
+                    };
 
-                if platform_matches(platforms) {
-                    match time::timeout(
-                        timeout_dur,
-                        assertion_step.run(value, &assertion_args, civ),
+                    let assertion_args = SegmentArgs::build(
+                        reference_assert,
+                        assertion,
+                        args,
+                        Some(&civ),
+                        Some(&*transient_placeholders),
                     )
-                    .await
+                    .map_err(|e| mark_and_return_step_error(e.into(), state))?;
+
+                    assertion_steps.push((
+                        reference_assert.get_as_string(),
+                        assertion_step,
+                        assertion_args,
+                        wrappers,
+                    ));
+                }
+
+                if platform_matches(platforms) && arch_matches(arch) {
+                    for (assertion_name, assertion_step, assertion_args, wrappers) in
+                        assertion_steps
                     {
-                        Ok(Ok(_)) => {}
-                        Ok(Err(e)) => {
-                            return Err(mark_and_return_step_error(e.into(), state));
-                        }
-                        Err(_) => {
-                            return Err(timeout_and_return_step_error(state));
+                        match time::timeout(
+                            timeout_dur,
+                            run_wrapped_assertion(
+                                wrappers,
+                                &assertion_name,
+                                *assertion_step,
+                                &assertion_args,
+                                value.clone(),
+                                civ,
+                            ),
+                        )
+                        .await
+                        {
+                            Ok(Ok(_)) => {}
+                            Ok(Err(e)) => {
+                                return Err(mark_and_return_step_error(e.into(), state));
+                            }
+                            Err(_) => {
+                                return Err(timeout_and_return_step_error(state));
+                            }
                         }
                     }
 
@@ -394,6 +753,8 @@ async fn run_toolproof_steps(
                 orig: _,
                 state,
                 platforms,
+                arch,
+                ..
             } => {
                 debugger_pause(&marked_base_step, civ);
 
@@ -411,23 +772,29 @@ async fn run_toolproof_steps(
                     snapshot,
                     args,
                     Some(&civ),
-                    transient_placeholders.as_ref(),
+                    Some(&*transient_placeholders),
                 )
                 .map_err(|e| mark_and_return_step_error(e.into(), state))?;
+                let retries = step_retries(reference_ret, civ);
 
-                if platform_matches(platforms) {
-                    let value =
-                        match time::timeout(timeout_dur, retrieval_step.run(&retrieval_args, civ))
-                            .await
-                        {
-                            Ok(Ok(val)) => val,
-                            Ok(Err(e)) => {
-                                return Err(mark_and_return_step_error(e.into(), state));
-                            }
-                            Err(_) => {
-                                return Err(timeout_and_return_step_error(state));
-                            }
-                        };
+                if platform_matches(platforms) && arch_matches(arch) {
+                    let value = match run_retrieval(
+                        *retrieval_step,
+                        &retrieval_args,
+                        retries,
+                        timeout_dur,
+                        civ,
+                    )
+                    .await
+                    {
+                        Ok(Ok(val)) => val,
+                        Ok(Err(e)) => {
+                            return Err(mark_and_return_step_error(e.into(), state));
+                        }
+                        Err(_) => {
+                            return Err(timeout_and_return_step_error(state));
+                        }
+                    };
 
                     let value_content = match &value {
                         serde_json::Value::String(s) => s.clone(),
@@ -442,11 +809,14 @@ async fn run_toolproof_steps(
             }
             crate::ToolproofTestStep::Extract {
                 extract,
-                extract_location,
+                extract_target,
+                extract_path,
                 args,
                 orig: _,
                 state,
                 platforms,
+                arch,
+                ..
             } => {
                 debugger_pause(&marked_base_step, civ);
 
@@ -464,31 +834,60 @@ async fn run_toolproof_steps(
                     extract,
                     args,
                     Some(&civ),
-                    transient_placeholders.as_ref(),
+                    Some(&*transient_placeholders),
                 )
                 .map_err(|e| mark_and_return_step_error(e.into(), state))?;
+                let retries = step_retries(reference_ret, civ);
 
-                if platform_matches(platforms) {
-                    let value =
-                        match time::timeout(timeout_dur, retrieval_step.run(&retrieval_args, civ))
-                            .await
-                        {
-                            Ok(Ok(val)) => val,
-                            Ok(Err(e)) => {
-                                return Err(mark_and_return_step_error(e.into(), state));
-                            }
-                            Err(_) => {
-                                return Err(timeout_and_return_step_error(state));
-                            }
-                        };
+                if platform_matches(platforms) && arch_matches(arch) {
+                    let value = match run_retrieval(
+                        *retrieval_step,
+                        &retrieval_args,
+                        retries,
+                        timeout_dur,
+                        civ,
+                    )
+                    .await
+                    {
+                        Ok(Ok(val)) => val,
+                        Ok(Err(e)) => {
+                            return Err(mark_and_return_step_error(e.into(), state));
+                        }
+                        Err(_) => {
+                            return Err(timeout_and_return_step_error(state));
+                        }
+                    };
 
-                    let value_content = match &value {
-                        serde_json::Value::String(s) => s.clone(),
-                        _ => serde_yaml::to_string(&value).expect("extract value is serializable"),
+                    let value = match extract_path {
+                        Some(path) => match select_extract_path(path, value) {
+                            Ok(value) => value,
+                            Err(e) => {
+                                return Err(mark_and_return_step_error(e, state));
+                            }
+                        },
+                        None => value,
                     };
 
-                    let location = retrieval_args.process_external_string(&extract_location);
-                    civ.write_file(&location, &value_content);
+                    match extract_target {
+                        ToolproofExtractTarget::File(location) => {
+                            let value_content = match &value {
+                                serde_json::Value::String(s) => s.clone(),
+                                _ => serde_yaml::to_string(&value)
+                                    .expect("extract value is serializable"),
+                            };
+
+                            let location = retrieval_args.process_external_string(location);
+                            civ.write_file(&location, &value_content);
+                        }
+                        ToolproofExtractTarget::Placeholder(name) => {
+                            let value_content = match &value {
+                                serde_json::Value::String(s) => s.clone(),
+                                _ => value.to_string(),
+                            };
+
+                            transient_placeholders.insert(name.clone(), value_content);
+                        }
+                    }
 
                     *state = ToolproofTestStepState::Passed;
                 } else {
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+
+use wax::{Glob, Pattern};
+
+use crate::options::ToolproofParams;
+
+/// The suffix used for new test files if one can't be taken from the `test_glob`
+const DEFAULT_TEST_SUFFIX: &str = ".toolproof.yml";
+
+/// Turns a test name into a file stem, such as `Checkout flow` into `checkout_flow`
+fn file_stem(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Finds the suffix that new test files need to be discovered by the `test_glob`,
+/// such as `.toolproof.yml` for `**/*.toolproof.yml`
+fn test_suffix(test_glob: &str) -> &str {
+    test_glob
+        .rsplit('/')
+        .next()
+        .and_then(|file| file.strip_prefix('*'))
+        .filter(|suffix| !suffix.is_empty() && !suffix.contains(['*', '?', '[', '{', '<']))
+        .unwrap_or(DEFAULT_TEST_SUFFIX)
+}
+
+fn test_template(name: &str) -> String {
+    // A JSON string is also a valid YAML string, and handles any quoting the name needs
+    let name = serde_json::to_string(name).expect("Strings serialize to JSON");
+
+    format!(
+        r#"name: {name}
+type: test
+
+steps:
+  # Replace these steps with your own. Steps are written as instructions,
+  # retrievals, and assertions, such as:
+  # - I have a "index.html" file with the content "<p>Hello World</p>"
+  - I run "echo 'Hello World'"
+  - stdout should contain "Hello World"
+"#
+    )
+}
+
+/// Writes a starter test file into the configured `root`,
+/// returning the path of the new file
+pub fn scaffold_test(params: &ToolproofParams, name: &str) -> Result<PathBuf, String> {
+    let stem = file_stem(name);
+    if stem.is_empty() {
+        return Err(format!(
+            "Can't create a test named \"{name}\", as it has no letters or numbers to name the file with"
+        ));
+    }
+
+    let filename = format!("{stem}{}", test_suffix(&params.test_glob));
+    let glob = Glob::new(&params.test_glob)
+        .map_err(|e| format!("Invalid test_glob \"{}\": {e}", params.test_glob))?;
+    if !glob.is_match(Path::new(&filename)) {
+        return Err(format!(
+            "Can't create {filename}, as it wouldn't be found by the test_glob \"{}\"",
+            params.test_glob
+        ));
+    }
+
+    let root = params.root.clone().unwrap_or_else(|| PathBuf::from("."));
+    let path = root.join(filename);
+    if path.exists() {
+        return Err(format!(
+            "{} already exists, and won't be overwritten",
+            path.display()
+        ));
+    }
+
+    std::fs::create_dir_all(&root)
+        .and_then(|_| std::fs::write(&path, test_template(name)))
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_new_test_filenames() {
+        assert_eq!(file_stem("Checkout flow"), "checkout_flow");
+        assert_eq!(file_stem("  API: users/list  "), "api_users_list");
+        assert_eq!(file_stem("!!!"), "");
+
+        assert_eq!(test_suffix("**/*.toolproof.yml"), ".toolproof.yml");
+        assert_eq!(test_suffix("tests/*.test.yaml"), ".test.yaml");
+        assert_eq!(test_suffix("**/*.{yml,yaml}"), DEFAULT_TEST_SUFFIX);
+    }
+}
@@ -5,14 +5,14 @@ use crate::{civilization::Civilization, errors::ToolproofInputError};
 use path_slash::{PathBufExt, PathExt};
 use serde_json::Value;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 pub enum ToolproofSegment {
     Literal(String),
     Value(serde_json::Value),
     Variable(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct ToolproofSegments {
     pub segments: Vec<ToolproofSegment>,
 }
@@ -85,6 +85,84 @@ impl ToolproofSegments {
             })
             .collect()
     }
+
+    /// For an assertion phrased as `not <assertion>`, returns the positive `<assertion>`
+    pub fn without_negation(&self) -> Option<ToolproofSegments> {
+        self.without_prefix(AssertionWrapper::Not.prefix())
+    }
+
+    fn without_prefix(&self, prefix: &str) -> Option<ToolproofSegments> {
+        use ToolproofSegment::*;
+
+        let Some((Literal(first), rest)) = self.segments.split_first() else {
+            return None;
+        };
+        let remaining = first.strip_prefix(prefix)?;
+
+        let mut segments = Vec::with_capacity(self.segments.len());
+        if !remaining.is_empty() {
+            segments.push(Literal(remaining.to_string()));
+        }
+        segments.extend(rest.iter().cloned());
+
+        Some(ToolproofSegments { segments })
+    }
+
+    /// Removes the phrasings that wrap another assertion (such as `not <assertion>`)
+    /// until reaching an assertion that is registered, or that can't be unwrapped any further.
+    /// Returns that assertion, and the wrappers that were removed from outermost to innermost.
+    pub fn unwrap_assertion(
+        &self,
+        is_registered: impl Fn(&ToolproofSegments) -> bool,
+    ) -> (ToolproofSegments, Vec<AssertionWrapper>) {
+        use AssertionWrapper::*;
+
+        let mut assertion = self.clone();
+        let mut wrappers = vec![];
+
+        while !is_registered(&assertion) {
+            let unwrapped = assertion
+                .without_negation()
+                .map(|inner| (Not, inner))
+                .or_else(|| {
+                    [AllItems, AnyItem].into_iter().find_map(|wrapper| {
+                        assertion
+                            .without_prefix(wrapper.prefix())
+                            .map(|inner| (wrapper, inner))
+                    })
+                });
+            let Some((wrapper, inner)) = unwrapped else {
+                break;
+            };
+
+            wrappers.push(wrapper);
+            assertion = inner;
+        }
+
+        (assertion, wrappers)
+    }
+}
+
+/// A phrasing that wraps another assertion, which the runner applies itself
+/// rather than it being registered as an assertion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssertionWrapper {
+    /// `not <assertion>`, which passes if the assertion fails
+    Not,
+    /// `have all items <assertion>`, which passes if the assertion passes for every item of an array
+    AllItems,
+    /// `have any item <assertion>`, which passes if the assertion passes for some item of an array
+    AnyItem,
+}
+
+impl AssertionWrapper {
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            AssertionWrapper::Not => "not ",
+            AssertionWrapper::AllItems => "have all items ",
+            AssertionWrapper::AnyItem => "have any item ",
+        }
+    }
 }
 
 fn has_args_string<V>(args: &HashMap<String, V>) -> String {
@@ -97,6 +175,9 @@ fn has_args_string<V>(args: &HashMap<String, V>) -> String {
 
 pub struct SegmentArgs<'a> {
     args: HashMap<String, &'a serde_json::Value>,
+    /// Arguments supplied alongside a step that aren't part of the step's text,
+    /// which are only visible to steps that read them as optional arguments
+    optional_args: HashMap<String, &'a serde_json::Value>,
     placeholder_delim: String,
     placeholders: HashMap<String, String>,
 }
@@ -105,6 +186,7 @@ impl<'a> SegmentArgs<'a> {
     pub fn build_synthetic(args: HashMap<String, &'a serde_json::Value>) -> Self {
         Self {
             args,
+            optional_args: HashMap::new(),
             placeholder_delim: "INTENTIONALLY_UNSET".to_string(),
             placeholders: HashMap::new(),
         }
@@ -145,11 +227,41 @@ impl<'a> SegmentArgs<'a> {
             }
         }
 
+        let optional_args = supplied_args
+            .iter()
+            .filter(|(key, _)| !args.contains_key(*key))
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect();
+
+        let mut segment_args = Self::with_placeholders(args, civ, transient_placeholders);
+        segment_args.optional_args = optional_args;
+        Ok(segment_args)
+    }
+
+    /// Uses every supplied argument as-is, for steps (such as `ref` steps)
+    /// that take arguments without any segments to match against
+    pub fn build_from_args(
+        supplied_args: &'a HashMap<String, serde_json::Value>,
+        civ: Option<&Civilization>,
+        transient_placeholders: Option<&HashMap<String, String>>,
+    ) -> SegmentArgs<'a> {
+        let args = supplied_args.iter().map(|(k, v)| (k.clone(), v)).collect();
+
+        Self::with_placeholders(args, civ, transient_placeholders)
+    }
+
+    fn with_placeholders(
+        args: HashMap<String, &'a serde_json::Value>,
+        civ: Option<&Civilization>,
+        transient_placeholders: Option<&HashMap<String, String>>,
+    ) -> SegmentArgs<'a> {
         let mut placeholders = civ
             .map(|c| c.universe.ctx.params.placeholders.clone())
             .unwrap_or_default();
 
         if let Some(civ) = civ {
+            placeholders.insert("toolproof_unique".to_string(), civ.unique_id.clone());
+
             placeholders.insert(
                 "toolproof_process_directory".to_string(),
                 civ.universe
@@ -192,13 +304,14 @@ impl<'a> SegmentArgs<'a> {
             placeholders.extend(transient_placeholders.clone().into_iter());
         }
 
-        Ok(Self {
+        Self {
             args,
+            optional_args: HashMap::new(),
             placeholders,
             placeholder_delim: civ
                 .map(|c| c.universe.ctx.params.placeholder_delimiter.clone())
                 .unwrap_or_default(),
-        })
+        }
     }
 
     pub fn get_value(&self, k: impl AsRef<str>) -> Result<serde_json::Value, ToolproofInputError> {
@@ -242,6 +355,70 @@ impl<'a> SegmentArgs<'a> {
         });
     }
 
+    /// Reads an argument that the step doesn't require, such as an argument given
+    /// alongside the step that isn't part of the step's text
+    pub fn get_optional_value(&self, k: impl AsRef<str>) -> Option<serde_json::Value> {
+        let value = self
+            .args
+            .get(k.as_ref())
+            .or_else(|| self.optional_args.get(k.as_ref()))?;
+
+        let mut value = (*value).clone();
+        replace_inside_value(&mut value, &self.placeholder_delim, &self.placeholders);
+
+        Some(value)
+    }
+
+    pub fn get_optional_string(
+        &self,
+        k: impl AsRef<str>,
+    ) -> Result<Option<String>, ToolproofInputError> {
+        match self.get_optional_value(k.as_ref()) {
+            None => Ok(None),
+            Some(Value::String(st)) => Ok(Some(st)),
+            Some(other) => Err(ToolproofInputError::IncorrectArgumentType {
+                arg: k.as_ref().to_string(),
+                was: match other {
+                    Value::Null => "null",
+                    Value::Bool(_) => "boolean",
+                    Value::Number(_) => "number",
+                    Value::Array(_) => "array",
+                    _ => "object",
+                }
+                .to_string(),
+                expected: "string".to_string(),
+            }),
+        }
+    }
+
+    /// Reads a number, which may also be given as a string when written inline
+    pub fn get_f64(&self, k: impl AsRef<str>) -> Result<f64, ToolproofInputError> {
+        let value = self.get_value(k.as_ref())?;
+
+        let number = match &value {
+            Value::Number(n) => n.as_f64(),
+            Value::String(s) => s.trim().parse::<f64>().ok(),
+            _ => None,
+        };
+
+        number.ok_or_else(|| {
+            let found = match &value {
+                Value::Null => "null".to_string(),
+                Value::Bool(_) => "boolean".to_string(),
+                Value::Number(_) => "number".to_string(),
+                Value::Array(_) => "array".to_string(),
+                Value::Object(_) => "object".to_string(),
+                Value::String(s) => format!("string ({s:?})"),
+            };
+
+            ToolproofInputError::IncorrectArgumentType {
+                arg: k.as_ref().to_string(),
+                was: found,
+                expected: "number".to_string(),
+            }
+        })
+    }
+
     /// Process an arbitrary string as if it were one of the contained arguments
     pub fn process_external_string(&self, raw_value: impl AsRef<str>) -> String {
         let mut value = Value::String(raw_value.as_ref().to_string());
@@ -285,12 +462,13 @@ fn replace_inside_value(value: &mut Value, delim: &str, placeholders: &HashMap<S
 #[cfg(test)]
 mod test {
 
-    use std::{collections::BTreeMap, sync::Arc};
+    use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
 
     use tokio::sync::OnceCell;
 
     use crate::{
         civilization::Civilization,
+        definitions::browser::BrowserEmulation,
         options::{ToolproofContext, ToolproofParams},
         parser::parse_segments,
         universe::Universe,
@@ -326,9 +504,10 @@ mod test {
         let instruction_def = parse_segments("I have a {name} file with the contents {var}")
             .expect("Valid instruction");
 
-        let user_instruction =
-            parse_segments("I have a \"%prefix%index.%ext%\" file with the contents ':)'")
-                .expect("Valid instruction");
+        let user_instruction = parse_segments(
+            "I have a \"%prefix%index.%ext%\" file with the contents '%toolproof_unique%'",
+        )
+        .expect("Valid instruction");
 
         let input = HashMap::new();
         let mut params = ToolproofParams::default();
@@ -355,12 +534,20 @@ mod test {
 
         let civ = Civilization {
             tmp_dir: None,
+            unique_id: "toolproof_test_id".to_string(),
             last_command_output: None,
+            last_response: None,
             assigned_server_port: None,
+            serving_https: false,
+            proxies: vec![],
             window: None,
+            emulation: BrowserEmulation::new(&universe.ctx.params),
             threads: vec![],
             handles: vec![],
             env_vars: HashMap::new(),
+            file_directory: PathBuf::from("."),
+            step_timeout: universe.ctx.params.timeout,
+            changed_images: vec![],
             universe: Arc::new(universe),
         };
 
@@ -380,6 +567,7 @@ mod test {
             );
         };
         assert_eq!(str, "__index.pdf");
+        assert_eq!(args.get_string("var").unwrap(), "toolproof_test_id");
     }
 
     // Segments should alias to each other regardless of the contents of their
@@ -448,4 +636,35 @@ mod test {
 
         assert_eq!(end_value, expected_end_value);
     }
+
+    #[test]
+    fn test_removing_negation() {
+        let negated = parse_segments("not match the schema {schema}").expect("Valid assertion");
+        let positive = parse_segments("match the schema {schema}").expect("Valid assertion");
+
+        assert_eq!(negated.without_negation(), Some(positive));
+        assert_eq!(positive.without_negation(), None);
+    }
+
+    #[test]
+    fn test_unwrapping_assertions() {
+        let registered = parse_segments("contain {expected}").expect("Valid assertion");
+        let wrapped =
+            parse_segments("not have all items not contain {expected}").expect("Valid assertion");
+
+        let (assertion, wrappers) = wrapped.unwrap_assertion(|a| a == &registered);
+        assert_eq!(assertion, registered);
+        assert_eq!(
+            wrappers,
+            vec![
+                AssertionWrapper::Not,
+                AssertionWrapper::AllItems,
+                AssertionWrapper::Not
+            ]
+        );
+
+        let (assertion, wrappers) = registered.unwrap_assertion(|a| a == &registered);
+        assert_eq!(assertion, registered);
+        assert!(wrappers.is_empty());
+    }
 }
@@ -1,9 +1,87 @@
 use nondestructive::yaml;
 
-use crate::{ToolproofTestFile, ToolproofTestStep};
+use crate::{platforms::normalize_line_endings, ToolproofTestFile, ToolproofTestStep};
 
-pub fn write_yaml_snapshots(input_doc: &str, hydrated_file: &ToolproofTestFile) -> String {
-    let mut doc = yaml::from_slice(input_doc).expect("Input doc parses as YAML");
+/// How far snapshot lines are indented past their `snapshot_content` key,
+/// if the step doesn't already have a snapshot to take the indentation from
+const DEFAULT_SNAPSHOT_INDENT: usize = 2;
+
+/// Finds how far an existing snapshot's lines are indented past their key,
+/// so that rewriting the snapshot doesn't change the indentation the user chose.
+fn existing_snapshot_indent(doc: &yaml::Document, step_id: usize) -> Option<usize> {
+    let indent_of = |line: &str| line.len() - line.trim_start().len();
+
+    let root = doc.as_ref();
+    let steps = root.as_mapping()?.get("steps")?;
+    let steps = steps.as_sequence()?;
+    let step = steps.get(step_id)?;
+
+    let content = step.as_mapping()?.get("snapshot_content")?.to_string();
+    let content_indent = content.lines().nth(1).map(indent_of)?;
+
+    // The first line of the step is written without its indentation,
+    // so the key's indentation is only known if it comes later
+    let step = step.to_string();
+    let key_indent = step
+        .lines()
+        .skip(1)
+        .find(|line| line.trim_start().starts_with("snapshot_content:"))
+        .map(indent_of)?;
+
+    content_indent
+        .checked_sub(key_indent)
+        .filter(|indent| *indent > 0)
+}
+
+/// Maps each parsed step to the index of the entry in the file's `steps` that it came from,
+/// as nested lists of steps (e.g. aliases of a shared list) are flattened when parsing
+fn source_step_indexes(input_doc: &str) -> Vec<usize> {
+    fn step_count(entry: &serde_yaml::Value) -> usize {
+        match entry {
+            serde_yaml::Value::Sequence(entries) => entries.iter().map(step_count).sum(),
+            _ => 1,
+        }
+    }
+
+    let Ok(doc) = serde_yaml::from_str::<serde_yaml::Value>(input_doc) else {
+        return vec![];
+    };
+    let Some(steps) = doc.get("steps").and_then(|steps| steps.as_sequence()) else {
+        return vec![];
+    };
+
+    steps
+        .iter()
+        .enumerate()
+        .flat_map(|(index, entry)| std::iter::repeat(index).take(step_count(entry)))
+        .collect()
+}
+
+pub fn write_yaml_snapshots(
+    input_doc: &str,
+    hydrated_file: &ToolproofTestFile,
+) -> Result<String, String> {
+    let has_snapshots = hydrated_file.steps.iter().any(|step| {
+        matches!(
+            step,
+            ToolproofTestStep::Snapshot {
+                snapshot_content: Some(_),
+                ..
+            }
+        )
+    });
+    if !has_snapshots {
+        return Ok(input_doc.to_string());
+    }
+
+    let mut doc = yaml::from_slice(input_doc).map_err(|e| {
+        format!(
+            "Unable to write snapshots to {}, as it contains YAML that can't be edited \
+            (such as an anchor on a list or mapping): {e}",
+            hydrated_file.file_path
+        )
+    })?;
+    let source_indexes = source_step_indexes(input_doc);
 
     for (step_id, step) in hydrated_file.steps.iter().enumerate() {
         match step {
@@ -13,6 +91,13 @@ pub fn write_yaml_snapshots(input_doc: &str, hydrated_file: &ToolproofTestFile)
                 let Some(snapshot_content) = snapshot_content else {
                     continue;
                 };
+                let Some(&step_id) = source_indexes.get(step_id) else {
+                    continue;
+                };
+
+                let indent =
+                    existing_snapshot_indent(&doc, step_id).unwrap_or(DEFAULT_SNAPSHOT_INDENT);
+                let indent = " ".repeat(indent);
 
                 let mut step = doc
                     .as_mut()
@@ -29,7 +114,9 @@ pub fn write_yaml_snapshots(input_doc: &str, hydrated_file: &ToolproofTestFile)
 
                 step.insert_block(
                     "snapshot_content",
-                    snapshot_content.lines().map(|l| format!("  ╎{l}")),
+                    normalize_line_endings(snapshot_content)
+                        .lines()
+                        .map(|l| format!("{indent}╎{l}")),
                     yaml::Block::Literal(yaml::Chomp::Strip),
                 );
             }
@@ -37,5 +124,5 @@ pub fn write_yaml_snapshots(input_doc: &str, hydrated_file: &ToolproofTestFile)
         }
     }
 
-    doc.to_string()
+    Ok(doc.to_string())
 }
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use crate::definitions::{ToolproofAssertion, ToolproofInstruction, ToolproofRetriever};
+use crate::segments::ToolproofSegments;
+use crate::ToolproofMacroFile;
+
+/// Every step that can be written in a test, grouped by kind and sorted,
+/// so that the list stays in sync with the registered steps
+pub struct StepList {
+    pub sections: Vec<(&'static str, Vec<String>)>,
+}
+
+impl StepList {
+    pub fn new(
+        instructions: &HashMap<ToolproofSegments, &'static dyn ToolproofInstruction>,
+        retrievers: &HashMap<ToolproofSegments, &'static dyn ToolproofRetriever>,
+        assertions: &HashMap<ToolproofSegments, &'static dyn ToolproofAssertion>,
+        macros: &HashMap<ToolproofSegments, ToolproofMacroFile>,
+    ) -> Self {
+        let sorted = |mut steps: Vec<String>| {
+            steps.sort_by_key(|step| step.to_lowercase());
+            steps
+        };
+
+        Self {
+            sections: vec![
+                (
+                    "Instructions",
+                    sorted(instructions.values().map(|i| i.segments().into()).collect()),
+                ),
+                (
+                    "Retrievals",
+                    sorted(retrievers.values().map(|r| r.segments().into()).collect()),
+                ),
+                (
+                    "Assertions",
+                    sorted(assertions.values().map(|a| a.segments().into()).collect()),
+                ),
+                (
+                    "Macros",
+                    sorted(macros.values().map(|m| m.macro_orig.clone()).collect()),
+                ),
+            ],
+        }
+    }
+
+    /// Keeps the steps containing `filter`, ignoring case
+    pub fn filtered(self, filter: &str) -> Self {
+        let filter = filter.to_lowercase();
+
+        Self {
+            sections: self
+                .sections
+                .into_iter()
+                .map(|(title, steps)| {
+                    let steps = steps
+                        .into_iter()
+                        .filter(|step| step.to_lowercase().contains(&filter))
+                        .collect();
+                    (title, steps)
+                })
+                .collect(),
+        }
+    }
+
+    /// Every step alongside the kind of step it is, such as `Instruction`
+    pub fn entries(&self) -> Vec<(&'static str, &String)> {
+        self.sections
+            .iter()
+            .flat_map(|(title, steps)| {
+                let kind = title.trim_end_matches('s');
+                steps.iter().map(move |step| (kind, step))
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.iter().all(|(_, steps)| steps.is_empty())
+    }
+
+    pub fn print(&self) {
+        let mut first = true;
+        for (title, steps) in self.sections.iter().filter(|(_, s)| !s.is_empty()) {
+            if !first {
+                println!();
+            }
+            first = false;
+
+            println!("{title}:");
+            for step in steps {
+                println!("  {step}");
+            }
+        }
+    }
+}
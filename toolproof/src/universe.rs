@@ -12,7 +12,9 @@ use crate::{
 };
 
 pub struct Universe<'u> {
-    pub browser: OnceCell<BrowserTester>,
+    /// The browser is launched by the first test that needs it. If launching fails,
+    /// the error is kept so that later tests fail quickly rather than retrying the launch.
+    pub browser: OnceCell<Result<BrowserTester, String>>,
     pub tests: BTreeMap<String, ToolproofTestFile>,
     pub macros: HashMap<ToolproofSegments, ToolproofMacroFile>,
     pub macro_comparisons: Vec<String>,